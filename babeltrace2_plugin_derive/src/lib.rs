@@ -0,0 +1,432 @@
+// Copyright 2023 - 2023, Karsten Becker and the rust babeltrace2-plugin contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+//! Derive macros of `babeltrace2_plugin`, they are re-exported and documented there
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, parse_quote, Attribute, Data, DeriveInput, Error, Fields, Generics, Ident,
+    Index, LitByteStr, LitStr, Path, Result, Type,
+};
+
+#[proc_macro_derive(BtPayload, attributes(bt))]
+pub fn derive_bt_payload(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    bt_payload(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+#[proc_macro_derive(FromBtField, attributes(bt))]
+pub fn derive_from_bt_field(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    from_bt_field(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// The options of a field or variant, given with `#[bt(...)]`
+#[derive(Default)]
+struct Attrs {
+    rename: Option<String>,
+    skip: bool,
+    display_base: Option<Ident>,
+}
+
+fn parse_attrs(attrs: &[Attribute]) -> Result<Attrs> {
+    let mut result = Attrs::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("bt")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                result.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("skip") {
+                result.skip = true;
+            } else if meta.path.is_ident("display_base") {
+                let base: LitStr = meta.value()?.parse()?;
+                let variant = match base.value().as_str() {
+                    "binary" => "Binary",
+                    "octal" => "Octal",
+                    "decimal" => "Decimal",
+                    "hexadecimal" => "Hexadecimal",
+                    _ => return Err(meta.error("expected binary, octal, decimal or hexadecimal")),
+                };
+                result.display_base = Some(Ident::new(variant, base.span()));
+            } else {
+                return Err(meta.error("expected rename, skip or display_base"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(result)
+}
+
+/// A field of a struct or variant which becomes a structure member
+struct Member {
+    name: String,
+    /// The expression of a reference to the value of the field
+    value: TokenStream2,
+    ty: Type,
+    display_base: Option<Ident>,
+}
+
+/// The members of `fields`, `value` turns the name or index of a field into a reference to it
+fn members(fields: &Fields, value: impl Fn(&Ident, usize) -> TokenStream2) -> Result<Vec<Member>> {
+    let mut members = Vec::new();
+    for (index, field) in fields.iter().enumerate() {
+        let attrs = parse_attrs(&field.attrs)?;
+        if attrs.skip {
+            continue;
+        }
+        let ident = field
+            .ident
+            .clone()
+            .unwrap_or_else(|| format_ident!("field_{}", index));
+        let name = match (attrs.rename, &field.ident) {
+            (Some(rename), _) => rename,
+            (None, Some(ident)) => ident.to_string(),
+            (None, None) => index.to_string(),
+        };
+        members.push(Member {
+            name,
+            value: value(&ident, index),
+            ty: field.ty.clone(),
+            display_base: attrs.display_base,
+        });
+    }
+    Ok(members)
+}
+
+/// The structure field class of `members`
+fn structure_spec(members: &[Member]) -> TokenStream2 {
+    let members = members.iter().map(|member| {
+        let Member {
+            name,
+            ty,
+            display_base,
+            ..
+        } = member;
+        // Only integers have a display base, other types fail to compile
+        let spec = match display_base {
+            Some(base) => quote! {
+                <#ty as ::babeltrace2_plugin::payload::BtIntegerPayload>
+                    ::field_class_spec_with_display_base(
+                        ::babeltrace2_plugin::bt2::BtFieldClassIntegerPreferredDisplayBase::#base,
+                    )
+            },
+            None => quote!(<#ty as ::babeltrace2_plugin::payload::BtPayload>::field_class_spec()),
+        };
+        quote!(.member(#name, #spec))
+    });
+    quote! {
+        ::babeltrace2_plugin::field_class::FieldClassSpec::structure()#(#members)*
+    }
+}
+
+/// Write `members` into the structure field `field`
+fn write_members(field: &Ident, members: &[Member]) -> TokenStream2 {
+    let writes = members.iter().enumerate().map(|(index, member)| {
+        let index = index as u64;
+        let value = &member.value;
+        quote! {
+            ::babeltrace2_plugin::payload::BtPayload::write_to(
+                #value,
+                &mut #field.structure_borrow_member_field_by_index(#index),
+            )?;
+        }
+    });
+    quote!(#(#writes)*)
+}
+
+fn add_bounds(mut generics: Generics, bound: Path) -> Generics {
+    for param in generics.type_params_mut() {
+        param.bounds.push(parse_quote!(#bound));
+    }
+    generics
+}
+
+/// The name of a variant as label or option name
+fn variant_name(variant: &syn::Variant) -> Result<String> {
+    let attrs = parse_attrs(&variant.attrs)?;
+    if attrs.skip || attrs.display_base.is_some() {
+        return Err(Error::new_spanned(variant, "variants can only be renamed"));
+    }
+    Ok(attrs.rename.unwrap_or_else(|| variant.ident.to_string()))
+}
+
+fn bt_payload(input: DeriveInput) -> Result<TokenStream2> {
+    // Mixed site identifiers do not clash with the bindings of the fields
+    let field = Ident::new("field", Span::mixed_site());
+    // Enumerations are integers, with a display base
+    let (spec, write, integer) = match &input.data {
+        Data::Struct(data) => {
+            let members = members(&data.fields, |ident, index| match data.fields {
+                Fields::Named(_) => quote!(&self.#ident),
+                _ => {
+                    let index = Index::from(index);
+                    quote!(&self.#index)
+                }
+            })?;
+            (structure_spec(&members), write_members(&field, &members), false)
+        }
+        Data::Enum(data) if data.variants.iter().all(|variant| variant.fields.is_empty()) => {
+            // Fieldless enums become signed enumerations with the discriminants as values
+            let mut labels = Vec::new();
+            let mut values = Vec::new();
+            let mut last_discriminant = None;
+            let mut offset = 0i64;
+            for variant in &data.variants {
+                labels.push(variant_name(variant)?);
+                if let Some((_, discriminant)) = &variant.discriminant {
+                    last_discriminant = Some(discriminant);
+                    offset = 0;
+                }
+                values.push(match last_discriminant {
+                    Some(discriminant) => quote!((#discriminant) as i64 + #offset),
+                    None => quote!(#offset),
+                });
+                offset += 1;
+            }
+            let idents = data.variants.iter().map(|variant| &variant.ident);
+            let spec = quote! {
+                ::babeltrace2_plugin::field_class::FieldClassSpec::signed_enumeration(64)
+                    #(.mapping(#labels, ::babeltrace2_plugin::field_class::Ranges::Signed(vec![#values..=#values])))*
+            };
+            let write = quote! {
+                #field.integer_signed_set_value(match self {
+                    #(Self::#idents => #values,)*
+                });
+            };
+            (spec, write, true)
+        }
+        Data::Enum(data) => {
+            // Enums with fields become variants without selector field, with a structure option
+            // for each enum variant
+            let mut options = Vec::new();
+            let mut arms = Vec::new();
+            for (index, variant) in data.variants.iter().enumerate() {
+                let name = variant_name(variant)?;
+                let members = members(&variant.fields, |ident, _| quote!(#ident))?;
+                let spec = structure_spec(&members);
+                options.push(quote!(.variant_option(#name, #spec, None)));
+
+                let ident = &variant.ident;
+                let bindings = members.iter().map(|member| &member.value);
+                let pattern = match &variant.fields {
+                    Fields::Named(_) => quote!(Self::#ident { #(#bindings,)* .. }),
+                    Fields::Unnamed(fields) => {
+                        let bindings = fields.unnamed.iter().enumerate().map(|(index, field)| {
+                            match parse_attrs(&field.attrs).map(|attrs| attrs.skip) {
+                                Ok(true) => quote!(_),
+                                _ => {
+                                    let binding = format_ident!("field_{}", index);
+                                    quote!(#binding)
+                                }
+                            }
+                        });
+                        quote!(Self::#ident(#(#bindings),*))
+                    }
+                    Fields::Unit => quote!(Self::#ident),
+                };
+                let index = index as u64;
+                let option = Ident::new("option", Span::mixed_site());
+                let write_option = if members.is_empty() {
+                    quote!()
+                } else {
+                    let write = write_members(&option, &members);
+                    quote! {
+                        let mut #option = #field.variant_borrow_selected_option_field();
+                        #write
+                    }
+                };
+                arms.push(quote! {
+                    #pattern => {
+                        #field.try_variant_select_option_by_index(#index)?;
+                        #write_option
+                    }
+                });
+            }
+            let spec = quote! {
+                ::babeltrace2_plugin::field_class::FieldClassSpec::variant(None)#(#options)*
+            };
+            let write = quote! {
+                match self {
+                    #(#arms)*
+                }
+            };
+            (spec, write, false)
+        }
+        Data::Union(_) => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "BtPayload cannot be derived for unions",
+            ))
+        }
+    };
+
+    let ident = &input.ident;
+    let generics = add_bounds(
+        input.generics.clone(),
+        parse_quote!(::babeltrace2_plugin::payload::BtPayload),
+    );
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let integer_impl = integer.then(|| {
+        quote! {
+            impl #impl_generics ::babeltrace2_plugin::payload::BtIntegerPayload
+                for #ident #ty_generics #where_clause {}
+        }
+    });
+    Ok(quote! {
+        impl #impl_generics ::babeltrace2_plugin::payload::BtPayload for #ident #ty_generics #where_clause {
+            fn field_class_spec() -> ::babeltrace2_plugin::field_class::FieldClassSpec {
+                #spec
+            }
+
+            #[allow(unused_variables)]
+            fn write_to(
+                &self,
+                #field: &mut ::babeltrace2_plugin::bt2::BtFieldRef<'_>,
+            ) -> ::babeltrace2_plugin::error::Result<()> {
+                #write
+                Ok(())
+            }
+        }
+
+        #integer_impl
+    })
+}
+
+/// Read the structure `field`, a reference to a field, into the fields of the struct or
+/// variant `path`
+fn read_fields(path: TokenStream2, fields: &Fields, field: TokenStream2) -> Result<TokenStream2> {
+    let mut values = Vec::new();
+    for (index, member) in fields.iter().enumerate() {
+        let attrs = parse_attrs(&member.attrs)?;
+        if attrs.skip {
+            values.push(quote!(::std::default::Default::default()));
+            continue;
+        }
+        let name = match (attrs.rename, &member.ident) {
+            (Some(rename), _) => rename,
+            (None, Some(ident)) => ident.to_string(),
+            (None, None) => index.to_string(),
+        };
+        values.push(quote! {
+            ::babeltrace2_plugin::payload::FromBtField::from_bt_field(
+                &*::babeltrace2_plugin::payload::borrow_member(#field, #name)?,
+            )?
+        });
+    }
+    let construct = match fields {
+        Fields::Named(_) => {
+            let idents = fields.iter().map(|member| &member.ident);
+            quote!(#path { #(#idents: #values),* })
+        }
+        Fields::Unnamed(_) => quote!(#path(#(#values),*)),
+        Fields::Unit => quote!(#path),
+    };
+    Ok(quote! {
+        {
+            ::babeltrace2_plugin::payload::check_class_type(
+                #field,
+                &[::babeltrace2_plugin::bt2::BtFieldClassType::Structure],
+            )?;
+            #construct
+        }
+    })
+}
+
+fn from_bt_field(input: DeriveInput) -> Result<TokenStream2> {
+    let field = Ident::new("field", Span::mixed_site());
+    let no_match = quote! {
+        ::babeltrace2_plugin::error::Error::new(
+            ::babeltrace2_plugin::error::ErrorKind::NoMatch,
+            "FromBtField",
+        )
+    };
+    let read = match &input.data {
+        Data::Struct(data) => {
+            let read = read_fields(quote!(Self), &data.fields, quote!(#field))?;
+            quote!(Ok(#read))
+        }
+        Data::Enum(data) if data.variants.iter().all(|variant| variant.fields.is_empty()) => {
+            // The first label of the value matching a variant selects it
+            let mut labels = Vec::new();
+            for variant in &data.variants {
+                let name = variant_name(variant)?;
+                labels.push(LitByteStr::new(name.as_bytes(), variant.ident.span()));
+            }
+            let idents = data.variants.iter().map(|variant| &variant.ident);
+            quote! {
+                use ::babeltrace2_plugin::bt2::BtFieldClassType;
+                ::babeltrace2_plugin::payload::check_class_type(
+                    #field,
+                    &[BtFieldClassType::UnsignedEnumeration, BtFieldClassType::SignedEnumeration],
+                )?;
+                let labels = match #field.get_class_type() {
+                    BtFieldClassType::UnsignedEnumeration => {
+                        #field.try_enumeration_unsigned_get_mapping_labels()?
+                    }
+                    _ => #field.try_enumeration_signed_get_mapping_labels()?,
+                };
+                labels
+                    .iter()
+                    .find_map(|label| match label.to_bytes() {
+                        #(#labels => Some(Self::#idents),)*
+                        _ => None,
+                    })
+                    .ok_or_else(|| #no_match)
+            }
+        }
+        Data::Enum(data) => {
+            // The name of the selected option selects the variant
+            let option = Ident::new("option", Span::mixed_site());
+            let mut arms = Vec::new();
+            for variant in &data.variants {
+                let name = variant_name(variant)?;
+                let name = LitByteStr::new(name.as_bytes(), variant.ident.span());
+                let ident = &variant.ident;
+                let read = read_fields(quote!(Self::#ident), &variant.fields, quote!(&#option))?;
+                arms.push(quote!(#name => Ok(#read),));
+            }
+            quote! {
+                use ::babeltrace2_plugin::bt2::BtFieldClassType;
+                ::babeltrace2_plugin::payload::check_class_type(
+                    #field,
+                    &[
+                        BtFieldClassType::VariantWithoutSelectorField,
+                        BtFieldClassType::VariantWithUnsignedIntegerSelectorField,
+                        BtFieldClassType::VariantWithSignedIntegerSelectorField,
+                    ],
+                )?;
+                let option_class = #field.variant_borrow_selected_option_class_const();
+                let #option = #field.variant_borrow_selected_option_field_const();
+                match option_class.get_name().to_bytes() {
+                    #(#arms)*
+                    _ => Err(#no_match),
+                }
+            }
+        }
+        Data::Union(_) => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "FromBtField cannot be derived for unions",
+            ))
+        }
+    };
+
+    let ident = &input.ident;
+    let generics = add_bounds(
+        input.generics.clone(),
+        parse_quote!(::babeltrace2_plugin::payload::FromBtField),
+    );
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::babeltrace2_plugin::payload::FromBtField for #ident #ty_generics #where_clause {
+            fn from_bt_field(
+                #field: &::babeltrace2_plugin::bt2::BtFieldConst,
+            ) -> ::babeltrace2_plugin::error::Result<Self> {
+                #read
+            }
+        }
+    })
+}
@@ -1,4 +1,128 @@
 // Copyright 2023 - 2023, Karsten Becker and the rust babeltrace2-plugin contributors
 // SPDX-License-Identifier: GPL-2.0-or-later
 
-// Put impl for types in the bt2 here
\ No newline at end of file
+// Put impl for types in the bt2 here
+
+/// Strings up to this length, without the NUL terminator, are converted on the stack
+const STACK_C_STR_LEN: usize = 127;
+
+/// A string which can be passed to libbabeltrace2. The functions of the bindings take
+/// `&str`, `String`, `&CStr` or `CString`, and add the NUL terminator when needed.
+pub trait AsCStr {
+    /// Get the string as NUL-terminated string. Fails if the string contains a NUL character.
+    fn as_c_str(&self) -> Result<CStrBuf<'_>, std::ffi::NulError>;
+}
+
+/// A NUL-terminated string, either borrowed, copied to the stack, or copied to the heap
+pub enum CStrBuf<'a> {
+    Borrowed(&'a CStr),
+    Stack { buf: [u8; STACK_C_STR_LEN + 1], len: usize },
+    Heap(std::ffi::CString),
+}
+
+impl<'a> std::ops::Deref for CStrBuf<'a> {
+    type Target = CStr;
+    fn deref(&self) -> &CStr {
+        match self {
+            CStrBuf::Borrowed(c_str) => c_str,
+            // The buffer was checked for NUL characters before the terminator was added
+            CStrBuf::Stack { buf, len } => unsafe { CStr::from_bytes_with_nul_unchecked(&buf[..len + 1]) },
+            CStrBuf::Heap(c_string) => c_string,
+        }
+    }
+}
+
+impl AsCStr for str {
+    fn as_c_str(&self) -> Result<CStrBuf<'_>, std::ffi::NulError> {
+        let bytes = self.as_bytes();
+        if bytes.len() > STACK_C_STR_LEN || bytes.contains(&0) {
+            //CString reports the position of the NUL character
+            return std::ffi::CString::new(bytes).map(CStrBuf::Heap);
+        }
+        let mut buf = [0; STACK_C_STR_LEN + 1];
+        buf[..bytes.len()].copy_from_slice(bytes);
+        Ok(CStrBuf::Stack { buf, len: bytes.len() })
+    }
+}
+
+impl AsCStr for String {
+    fn as_c_str(&self) -> Result<CStrBuf<'_>, std::ffi::NulError> {
+        self.as_str().as_c_str()
+    }
+}
+
+impl AsCStr for CStr {
+    fn as_c_str(&self) -> Result<CStrBuf<'_>, std::ffi::NulError> {
+        Ok(CStrBuf::Borrowed(self))
+    }
+}
+
+impl AsCStr for std::ffi::CString {
+    fn as_c_str(&self) -> Result<CStrBuf<'_>, std::ffi::NulError> {
+        Ok(CStrBuf::Borrowed(self.as_c_str()))
+    }
+}
+
+impl<T: AsCStr + ?Sized> AsCStr for &T {
+    fn as_c_str(&self) -> Result<CStrBuf<'_>, std::ffi::NulError> {
+        (**self).as_c_str()
+    }
+}
+
+impl<'a> BtEventConstRef<'a> {
+    /// The stream of the event, borrowed for as long as the event
+    pub fn into_stream_const(self) -> BtStreamConstRef<'a> {
+        let ptr = unsafe { bt_event_borrow_stream_const(self.inner.ptr) };
+        BtStreamConstRef { inner: BtStreamConst { ptr }, phantom: std::marker::PhantomData }
+    }
+
+    /// The packet of the event, borrowed for as long as the event. None if the stream has no
+    /// packets.
+    pub fn into_packet_const(self) -> Option<BtPacketConstRef<'a>> {
+        let ptr = unsafe { bt_event_borrow_packet_const(self.inner.ptr) };
+        (!ptr.is_null()).then_some(BtPacketConstRef { inner: BtPacketConst { ptr }, phantom: std::marker::PhantomData })
+    }
+}
+
+impl BtSelfComponent {
+    /// The component as filter component, without checking its class type
+    ///
+    /// # Safety
+    ///
+    /// The component has to be a filter, see `BtComponentConst::is_filter_inline`.
+    pub(crate) unsafe fn as_self_component_filter_unchecked(&self) -> BtSelfComponentFilter {
+        BtSelfComponentFilter { ptr: self.ptr as *mut bt_self_component_filter }
+    }
+}
+
+impl<'a> BtValueConstRef<'a> {
+    /// Borrow the value `ptr`, which has to live at least as long as `'a`
+    pub(crate) unsafe fn borrow_ptr(ptr: *const bt_value) -> Self {
+        BtValueConstRef { inner: BtValueConst { ptr }, phantom: std::marker::PhantomData }
+    }
+}
+
+impl<'a> BtPacketConstRef<'a> {
+    /// The stream of the packet, borrowed for as long as the packet
+    pub fn into_stream_const(self) -> BtStreamConstRef<'a> {
+        let ptr = unsafe { bt_packet_borrow_stream_const(self.inner.ptr) };
+        BtStreamConstRef { inner: BtStreamConst { ptr }, phantom: std::marker::PhantomData }
+    }
+}
+
+/// A string of libbabeltrace2 which is NULL when not set
+unsafe fn optional_c_str<'a>(ptr: *const std::os::raw::c_char) -> Option<&'a CStr> {
+    (!ptr.is_null()).then(|| CStr::from_ptr(ptr))
+}
+
+impl BtEventClassConst {
+    /// The name of the event class, None if it has no name
+    pub fn get_optional_name(&self) -> Option<&CStr> {
+        unsafe { optional_c_str(bt_event_class_get_name(self.ptr)) }
+    }
+
+    /// The EMF URI of the event class, None if it has none
+    pub fn get_optional_emf_uri(&self) -> Option<&CStr> {
+        unsafe { optional_c_str(bt_event_class_get_emf_uri(self.ptr)) }
+    }
+}
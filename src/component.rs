@@ -0,0 +1,147 @@
+// Copyright 2023 - 2023, Karsten Becker and the rust babeltrace2-plugin contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+//! The parts of the trampolines shared by the source, filter and sink component classes
+//!
+//! The trampolines of [`source`](crate::source), [`filter`](crate::filter) and
+//! [`sink`](crate::sink) wrap the pointers of their component kind and pass the method of the
+//! trait to these helpers. The helpers catch a panic of the method and return the error
+//! status instead, as a panic must not unwind into libbabeltrace2.
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::bt2::binding::*;
+use crate::bt2::*;
+use crate::current_thread::ErrorActor;
+use crate::params::{initialize_with, ParamSchema};
+use crate::{borrow_boxed_data, get_boxed_data, set_boxed_data, ToSelfComponent};
+
+/// Call `method` and return `error` if it panics
+pub(crate) fn catch_panic<R>(error: R, method: impl FnOnce() -> R) -> R {
+    panic::catch_unwind(AssertUnwindSafe(method)).unwrap_or(error)
+}
+
+/// Validate `params` with `schema`, call `initialize` and set the private data of the
+/// component to the result turned into the data by `into_data`.
+pub(crate) unsafe fn initialize<C, T, D>(
+    self_component: &mut C,
+    schema: Option<ParamSchema>,
+    params: *const bt_value,
+    initialize: impl FnOnce(
+        &mut C,
+        &BtValueConst,
+    ) -> Result<T, BtComponentClassInitializeMethodStatus>,
+    into_data: impl FnOnce(T) -> D,
+) -> bt_component_class_initialize_method_status
+where
+    C: ToSelfComponent + ErrorActor,
+{
+    let params = BtValueConst::from_ptr(params);
+    let result = catch_panic(Err(BtComponentClassInitializeMethodStatus::Error), || {
+        initialize_with(schema, self_component, &params, initialize)
+    });
+    match result {
+        Ok(data) => {
+            set_boxed_data(self_component, Box::new(into_data(data)));
+            BtComponentClassInitializeMethodStatus::Ok.into()
+        }
+        Err(status) => status.into(),
+    }
+}
+
+/// Take the private data of the component and call `finalize` with it, if it was set
+pub(crate) unsafe fn finalize<C: ToSelfComponent, D>(
+    self_component: &mut C,
+    finalize: impl FnOnce(&mut D, &mut C),
+) {
+    if self_component.to_self_component().get_data().is_null() {
+        return;
+    }
+    let mut data: Box<D> = get_boxed_data(self_component);
+    catch_panic((), || finalize(&mut data, self_component));
+}
+
+/// Call `method` with the private data of the component, `error` is returned if it panics
+pub(crate) unsafe fn with_data<C: ToSelfComponent, D, R>(
+    self_component: &mut C,
+    error: R,
+    method: impl FnOnce(&mut D, &mut C) -> R,
+) -> R {
+    let data: &mut D = borrow_boxed_data(self_component);
+    catch_panic(error, || method(data, self_component))
+}
+
+/// Call `query` and pass the reference of the returned value to `result`
+pub(crate) unsafe fn query<C>(
+    self_component_class: &mut C,
+    query_executor: *mut bt_private_query_executor,
+    object_name: *const c_char,
+    params: *const bt_value,
+    result: *mut *const bt_value,
+    query: impl FnOnce(
+        &mut C,
+        &mut BtPrivateQueryExecutor,
+        &CStr,
+        &BtValueConst,
+    ) -> Result<BtValueConstOwned, BtComponentClassQueryMethodStatus>,
+) -> bt_component_class_query_method_status {
+    let mut query_executor = BtPrivateQueryExecutor::from_ptr(query_executor);
+    let object_name = CStr::from_ptr(object_name);
+    let params = BtValueConst::from_ptr(params);
+    let value = catch_panic(Err(BtComponentClassQueryMethodStatus::Error), || {
+        query(
+            self_component_class,
+            &mut query_executor,
+            object_name,
+            &params,
+        )
+    });
+    match value {
+        Ok(value) => {
+            *result = value.into_inner().as_ptr();
+            BtComponentClassQueryMethodStatus::Ok.into()
+        }
+        Err(status) => status.into(),
+    }
+}
+
+/// Call `get_supported_mip_versions` with the wrapped arguments
+pub(crate) unsafe fn get_supported_mip_versions<C>(
+    self_component_class: &mut C,
+    params: *const bt_value,
+    logging_level: bt_logging_level,
+    supported_versions: *mut bt_integer_range_set_unsigned,
+    get_supported_mip_versions: impl FnOnce(
+        &mut C,
+        &BtValueConst,
+        BtLoggingLevel,
+        &mut BtIntegerRangeSetUnsigned,
+    ) -> BtComponentClassGetSupportedMipVersionsMethodStatus,
+) -> bt_component_class_get_supported_mip_versions_method_status {
+    let params = BtValueConst::from_ptr(params);
+    let mut supported_versions = BtIntegerRangeSetUnsigned::from_ptr(supported_versions);
+    let status = catch_panic(BtComponentClassGetSupportedMipVersionsMethodStatus::Error, || {
+        get_supported_mip_versions(
+            self_component_class,
+            &params,
+            logging_level.into(),
+            &mut supported_versions,
+        )
+    });
+    status.into()
+}
+
+/// The default of the `get_supported_mip_versions` methods: only version 0, just like
+/// without the method
+pub(crate) fn mip_version_0(
+    supported_versions: &mut BtIntegerRangeSetUnsigned,
+) -> BtComponentClassGetSupportedMipVersionsMethodStatus {
+    match supported_versions.add_range(0, 0) {
+        BtIntegerRangeSetAddRangeStatus::Ok => {
+            BtComponentClassGetSupportedMipVersionsMethodStatus::Ok
+        }
+        BtIntegerRangeSetAddRangeStatus::MemoryError => {
+            BtComponentClassGetSupportedMipVersionsMethodStatus::MemoryError
+        }
+    }
+}
@@ -0,0 +1,217 @@
+// Copyright 2023 - 2023, Karsten Becker and the rust babeltrace2-plugin contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+//! The error of the current thread
+//!
+//! libbabeltrace2 keeps one error per thread, which is made of causes. Before returning an
+//! error status, append a cause with [`bt_error_cause!`](crate::bt_error_cause), so
+//! `babeltrace2` can tell which component failed and why. An error reported by another
+//! component can be inspected with [`take_error`].
+use std::ffi::CString;
+use std::mem::ManuallyDrop;
+use std::os::raw::c_char;
+
+use crate::bt2::binding::*;
+use crate::bt2::*;
+
+/// The format string passing the message through unchanged
+const MESSAGE_FORMAT: &[u8] = b"%s\0";
+
+/// Something an error cause can be attributed to: a self component, a self component class,
+/// a self message iterator, or the name of a module for code unrelated to any of them.
+pub trait ErrorActor {
+    /// Append a cause to the error of the current thread. [`bt_error_cause!`](crate::bt_error_cause)
+    /// fills in the file name and line number.
+    fn append_error_cause(
+        &mut self,
+        file_name: &str,
+        line_no: u64,
+        message: &str,
+    ) -> BtCurrentThreadErrorAppendCauseStatus;
+}
+
+/// The messages are only informative, so NUL characters are escaped instead of failing
+fn to_cstring(text: &str) -> CString {
+    CString::new(text.replace('\0', "\\0")).unwrap()
+}
+
+impl ErrorActor for BtSelfComponent {
+    fn append_error_cause(
+        &mut self,
+        file_name: &str,
+        line_no: u64,
+        message: &str,
+    ) -> BtCurrentThreadErrorAppendCauseStatus {
+        let file_name = to_cstring(file_name);
+        let message = to_cstring(message);
+        unsafe {
+            bt_current_thread_error_append_cause_from_component(
+                self.as_ptr(),
+                file_name.as_ptr(),
+                line_no,
+                MESSAGE_FORMAT.as_ptr() as *const c_char,
+                message.as_ptr(),
+            )
+        }
+        .into()
+    }
+}
+
+impl ErrorActor for BtSelfComponentClass {
+    fn append_error_cause(
+        &mut self,
+        file_name: &str,
+        line_no: u64,
+        message: &str,
+    ) -> BtCurrentThreadErrorAppendCauseStatus {
+        let file_name = to_cstring(file_name);
+        let message = to_cstring(message);
+        unsafe {
+            bt_current_thread_error_append_cause_from_component_class(
+                self.as_ptr(),
+                file_name.as_ptr(),
+                line_no,
+                MESSAGE_FORMAT.as_ptr() as *const c_char,
+                message.as_ptr(),
+            )
+        }
+        .into()
+    }
+}
+
+impl ErrorActor for BtSelfMessageIterator {
+    fn append_error_cause(
+        &mut self,
+        file_name: &str,
+        line_no: u64,
+        message: &str,
+    ) -> BtCurrentThreadErrorAppendCauseStatus {
+        let file_name = to_cstring(file_name);
+        let message = to_cstring(message);
+        unsafe {
+            bt_current_thread_error_append_cause_from_message_iterator(
+                self.as_ptr(),
+                file_name.as_ptr(),
+                line_no,
+                MESSAGE_FORMAT.as_ptr() as *const c_char,
+                message.as_ptr(),
+            )
+        }
+        .into()
+    }
+}
+
+/// The name of the module the error happened in
+impl ErrorActor for &str {
+    fn append_error_cause(
+        &mut self,
+        file_name: &str,
+        line_no: u64,
+        message: &str,
+    ) -> BtCurrentThreadErrorAppendCauseStatus {
+        let module_name = to_cstring(self);
+        let file_name = to_cstring(file_name);
+        let message = to_cstring(message);
+        unsafe {
+            bt_current_thread_error_append_cause_from_unknown(
+                module_name.as_ptr(),
+                file_name.as_ptr(),
+                line_no,
+                MESSAGE_FORMAT.as_ptr() as *const c_char,
+                message.as_ptr(),
+            )
+        }
+        .into()
+    }
+}
+
+macro_rules! impl_error_actor_by_upcast {
+    ($($type:ty => $upcast:ident),*) => {
+        $(
+            impl ErrorActor for $type {
+                fn append_error_cause(
+                    &mut self,
+                    file_name: &str,
+                    line_no: u64,
+                    message: &str,
+                ) -> BtCurrentThreadErrorAppendCauseStatus {
+                    self.$upcast().append_error_cause(file_name, line_no, message)
+                }
+            }
+        )*
+    };
+}
+
+impl_error_actor_by_upcast!(
+    BtSelfComponentSource => as_self_component_inline,
+    BtSelfComponentFilter => as_self_component_inline,
+    BtSelfComponentSink => as_self_component_inline,
+    BtSelfComponentClassSource => as_self_component_class_inline,
+    BtSelfComponentClassFilter => as_self_component_class_inline,
+    BtSelfComponentClassSink => as_self_component_class_inline
+);
+
+/// Append a cause with a formatted message to the error of the current thread. The file
+/// name and line number are the ones of the macro call. The first argument is an
+/// [`ErrorActor`].
+///
+/// ```ignore
+/// bt_error_cause!(self_component, "Cannot open {}", path);
+/// return Err(BtComponentClassInitializeMethodStatus::Error);
+/// ```
+#[macro_export]
+macro_rules! bt_error_cause {
+    ($actor:expr, $($arg:tt)+) => {{
+        use $crate::current_thread::ErrorActor as _;
+        ($actor).append_error_cause(file!(), u64::from(line!()), &format!($($arg)+))
+    }};
+}
+
+/// Take the error of the current thread, if there is one. The current thread has no error
+/// afterwards.
+pub fn take_error() -> Option<TakenError> {
+    let error = unsafe { bt_current_thread_take_error() };
+    if error.is_null() {
+        None
+    } else {
+        Some(TakenError {
+            error: BtErrorConst::from_ptr(error),
+        })
+    }
+}
+
+/// Release the error of the current thread, if there is one.
+pub fn clear_error() {
+    unsafe { bt_current_thread_clear_error() }
+}
+
+/// An error taken from the current thread, which is released when dropped
+pub struct TakenError {
+    error: BtErrorConst,
+}
+
+impl TakenError {
+    /// The causes of the error
+    pub fn causes(&self) -> impl Iterator<Item = BtErrorCauseConstRef<'_>> {
+        (0..self.error.get_cause_count()).map(move |index| self.error.borrow_cause_by_index(index))
+    }
+
+    /// Give the error back to the current thread, so it is reported along with the next
+    /// error status.
+    pub fn move_to_current_thread(self) {
+        let taken = ManuallyDrop::new(self);
+        unsafe { bt_current_thread_move_error(taken.error.as_ptr()) }
+    }
+}
+
+impl std::ops::Deref for TakenError {
+    type Target = BtErrorConst;
+    fn deref(&self) -> &BtErrorConst {
+        &self.error
+    }
+}
+
+impl Drop for TakenError {
+    fn drop(&mut self) {
+        self.error.release();
+    }
+}
@@ -0,0 +1,90 @@
+// Copyright 2023 - 2023, Karsten Becker and the rust babeltrace2-plugin contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+//! Error type of the `Result` returning functions
+//!
+//! Every status enum of libbabeltrace2 can be turned into a [`Result`] with its generated
+//! `into_result()`, and every function returning a status has a `try_` variant doing so. The
+//! [`Error`] keeps the kind of the status, so `?` can be used without losing whether a call
+//! failed, or simply has to be tried again.
+use std::fmt;
+
+/// The non-Ok variants of the status enums, and the errors detected before calling libbabeltrace2
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ErrorKind {
+    MemoryError,
+    Error,
+    Again,
+    End,
+    NotFound,
+    NoMatch,
+    OverflowError,
+    UnknownObject,
+    Interrupt,
+    Interrupted,
+    UserError,
+    /// A string passed to libbabeltrace2 contains a NUL character
+    InteriorNul,
+    /// A field does not have the class type a value is read from
+    TypeMismatch,
+    /// The stream class has packets, but no packet was given
+    PacketRequired,
+    /// The stream class has no packets, but a packet was given
+    PacketNotSupported,
+    /// The packet given is not one of the stream given
+    PacketStreamMismatch,
+    /// The event class given is not one of the class of the stream given
+    EventClassStreamMismatch,
+    /// The stream class has default clock snapshots for the message, but none was given
+    ClockSnapshotRequired,
+    /// The stream class has no default clock snapshots for the message, but one was given
+    ClockSnapshotNotSupported,
+    /// The stream class does not support discarded events or packets messages
+    DiscardedNotSupported,
+    /// A clock snapshot is before the previous one of the stream
+    ClockSnapshotOutOfOrder,
+}
+
+/// A status other than Ok, together with the name of the status enum it originates from
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Error {
+    kind: ErrorKind,
+    status: &'static str,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind, status: &'static str) -> Error {
+        Error { kind, status }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// The name of the status enum, for example `BtGraphRunStatus`
+    pub fn status(&self) -> &'static str {
+        self.status
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}::{:?}", self.status, self.kind)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::ffi::NulError> for Error {
+    fn from(_: std::ffi::NulError) -> Error {
+        Error::new(ErrorKind::InteriorNul, "NulError")
+    }
+}
+
+/// For conversions which can't fail, such as `TryFrom` implemented through `From`
+impl From<std::convert::Infallible> for Error {
+    fn from(never: std::convert::Infallible) -> Error {
+        match never {}
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
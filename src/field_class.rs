@@ -0,0 +1,718 @@
+// Copyright 2023 - 2023, Karsten Becker and the rust babeltrace2-plugin contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+//! Describe field classes as a tree and create them in one call
+//!
+//! A [`FieldClassSpec`] is a plain Rust description of a field class. [`FieldClassSpec::build`]
+//! creates the field class, and all the field classes it contains, in a trace class and
+//! returns a [`FieldClassHandle`] tree mirroring the specification.
+//!
+//! Dynamic arrays, options and variants can refer to a length or selector field by name. The
+//! name is the one of a structure member created before, in the same structure or in one of
+//! the enclosing ones. The name has to be unique, and the member must not be in an array,
+//! option or variant option which doesn't also contain the referring field class, otherwise
+//! building fails with [`ErrorKind::UserError`]. The [`BuildError`] tells which field class
+//! failed to build, and which name could not be looked up.
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use crate::bt2::*;
+use crate::error::{Error, ErrorKind, Result};
+
+/// The range and display base of integer and enumeration field classes
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntegerSpec {
+    /// The number of bits of the values, 1 to 64
+    pub field_value_range: u64,
+    pub display_base: BtFieldClassIntegerPreferredDisplayBase,
+}
+
+impl Default for IntegerSpec {
+    fn default() -> Self {
+        IntegerSpec {
+            field_value_range: 64,
+            display_base: BtFieldClassIntegerPreferredDisplayBase::Decimal,
+        }
+    }
+}
+
+/// Integer ranges, for enumeration mappings and the options of selector fields
+#[derive(Debug, Clone, PartialEq)]
+pub enum Ranges {
+    Unsigned(Vec<RangeInclusive<u64>>),
+    Signed(Vec<RangeInclusive<i64>>),
+}
+
+/// How an option field class decides if its field is present
+#[derive(Debug, Clone, PartialEq)]
+pub enum OptionSelector {
+    /// The presence of the field is set when the field is created
+    None,
+    /// The field is present when the bool field `field` is true, or false if `reversed`
+    Bool { field: String, reversed: bool },
+    /// The field is present when the integer field `field` is within `ranges`
+    Integer { field: String, ranges: Ranges },
+}
+
+/// An option of a variant field class
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariantOptionSpec {
+    pub name: String,
+    pub field_class: FieldClassSpec,
+    /// The values of the selector field selecting this option, `None` without selector field
+    pub ranges: Option<Ranges>,
+}
+
+/// The description of a field class
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldClassSpec {
+    Bool,
+    /// A bit array of 1 to 64 bits
+    BitArray {
+        length: u64,
+    },
+    UnsignedInteger(IntegerSpec),
+    SignedInteger(IntegerSpec),
+    SinglePrecisionReal,
+    DoublePrecisionReal,
+    String,
+    /// An enumeration, the mappings are labels with their unsigned or signed ranges
+    Enumeration {
+        integer: IntegerSpec,
+        mappings: Vec<(String, Ranges)>,
+        signed: bool,
+    },
+    StaticArray {
+        element: Box<FieldClassSpec>,
+        length: u64,
+    },
+    /// A dynamic array, with the name of an unsigned integer field holding the length
+    DynamicArray {
+        element: Box<FieldClassSpec>,
+        length_field: Option<String>,
+    },
+    Option {
+        field_class: Box<FieldClassSpec>,
+        selector: OptionSelector,
+    },
+    /// A variant, with the name of an integer field selecting the option. The options have
+    /// ranges of the same signedness as the selector field, or none without selector field.
+    Variant {
+        options: Vec<VariantOptionSpec>,
+        selector_field: Option<String>,
+    },
+    Structure(Vec<(String, FieldClassSpec)>),
+}
+
+impl FieldClassSpec {
+    pub fn unsigned_integer(field_value_range: u64) -> Self {
+        FieldClassSpec::UnsignedInteger(IntegerSpec {
+            field_value_range,
+            ..Default::default()
+        })
+    }
+
+    pub fn signed_integer(field_value_range: u64) -> Self {
+        FieldClassSpec::SignedInteger(IntegerSpec {
+            field_value_range,
+            ..Default::default()
+        })
+    }
+
+    pub fn unsigned_enumeration(field_value_range: u64) -> Self {
+        FieldClassSpec::Enumeration {
+            integer: IntegerSpec {
+                field_value_range,
+                ..Default::default()
+            },
+            mappings: Vec::new(),
+            signed: false,
+        }
+    }
+
+    pub fn signed_enumeration(field_value_range: u64) -> Self {
+        FieldClassSpec::Enumeration {
+            integer: IntegerSpec {
+                field_value_range,
+                ..Default::default()
+            },
+            mappings: Vec::new(),
+            signed: true,
+        }
+    }
+
+    pub fn static_array(element: FieldClassSpec, length: u64) -> Self {
+        FieldClassSpec::StaticArray {
+            element: Box::new(element),
+            length,
+        }
+    }
+
+    /// A dynamic array, optionally with the name of the unsigned integer field holding the length
+    pub fn dynamic_array(element: FieldClassSpec, length_field: Option<&str>) -> Self {
+        FieldClassSpec::DynamicArray {
+            element: Box::new(element),
+            length_field: length_field.map(str::to_string),
+        }
+    }
+
+    pub fn option(field_class: FieldClassSpec, selector: OptionSelector) -> Self {
+        FieldClassSpec::Option {
+            field_class: Box::new(field_class),
+            selector,
+        }
+    }
+
+    /// A variant without options, optionally with the name of the integer field selecting the
+    /// option
+    pub fn variant(selector_field: Option<&str>) -> Self {
+        FieldClassSpec::Variant {
+            options: Vec::new(),
+            selector_field: selector_field.map(str::to_string),
+        }
+    }
+
+    /// A structure without members
+    pub fn structure() -> Self {
+        FieldClassSpec::Structure(Vec::new())
+    }
+
+    /// Set the display base of an integer or enumeration. Panics for other field classes.
+    pub fn display_base(mut self, display_base: BtFieldClassIntegerPreferredDisplayBase) -> Self {
+        match &mut self {
+            FieldClassSpec::UnsignedInteger(integer)
+            | FieldClassSpec::SignedInteger(integer)
+            | FieldClassSpec::Enumeration { integer, .. } => integer.display_base = display_base,
+            _ => panic!("Only integers and enumerations have a display base"),
+        }
+        self
+    }
+
+    /// Append a member to a structure. Panics for other field classes.
+    pub fn member(mut self, name: &str, field_class: FieldClassSpec) -> Self {
+        match &mut self {
+            FieldClassSpec::Structure(members) => members.push((name.to_string(), field_class)),
+            _ => panic!("Only structures have members"),
+        }
+        self
+    }
+
+    /// Add a mapping to an enumeration. Panics for other field classes.
+    pub fn mapping(mut self, label: &str, ranges: Ranges) -> Self {
+        match &mut self {
+            FieldClassSpec::Enumeration { mappings, .. } => {
+                mappings.push((label.to_string(), ranges))
+            }
+            _ => panic!("Only enumerations have mappings"),
+        }
+        self
+    }
+
+    /// Append an option to a variant, `ranges` are the values of the selector field selecting
+    /// it. Panics for other field classes.
+    pub fn variant_option(
+        mut self,
+        name: &str,
+        field_class: FieldClassSpec,
+        ranges: Option<Ranges>,
+    ) -> Self {
+        match &mut self {
+            FieldClassSpec::Variant { options, .. } => options.push(VariantOptionSpec {
+                name: name.to_string(),
+                field_class,
+                ranges,
+            }),
+            _ => panic!("Only variants have options"),
+        }
+        self
+    }
+
+    /// Create the field class in `trace_class`
+    pub fn build(&self, trace_class: &BtTraceClass) -> BuildResult<FieldClassHandle> {
+        Builder {
+            trace_class,
+            named: Vec::new(),
+            scope: Vec::new(),
+            next_scope: 0,
+        }
+        .build(self)
+    }
+}
+
+/// A field class created from a [`FieldClassSpec`], with the handles of the field classes it
+/// contains: the members of a structure, the options of a variant, or the single unnamed
+/// element of an array or option.
+pub struct FieldClassHandle {
+    field_class: BtFieldClassOwned,
+    children: Vec<(String, FieldClassHandle)>,
+}
+
+impl FieldClassHandle {
+    pub fn field_class(&self) -> &BtFieldClass {
+        &self.field_class
+    }
+
+    /// The handle of the member or option `name`
+    pub fn child(&self, name: &str) -> Option<&FieldClassHandle> {
+        self.children
+            .iter()
+            .find(|(child_name, _)| child_name == name)
+            .map(|(_, child)| child)
+    }
+
+    pub fn children(&self) -> impl Iterator<Item = (&str, &FieldClassHandle)> {
+        self.children
+            .iter()
+            .map(|(name, child)| (name.as_str(), child))
+    }
+
+    pub fn into_field_class(self) -> BtFieldClassOwned {
+        self.field_class
+    }
+}
+
+impl std::ops::Deref for FieldClassHandle {
+    type Target = BtFieldClass;
+    fn deref(&self) -> &BtFieldClass {
+        &self.field_class
+    }
+}
+
+/// An error of [`FieldClassSpec::build`], with the member and option names leading to the
+/// field class causing it. The element of an array is `[]` in the path, and the field class
+/// of an option is `?`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildError {
+    error: Error,
+    path: Vec<String>,
+    message: String,
+}
+
+impl BuildError {
+    fn new(kind: ErrorKind, message: String) -> BuildError {
+        BuildError {
+            error: Error::new(kind, "FieldClassSpec"),
+            path: Vec::new(),
+            message,
+        }
+    }
+
+    fn in_name(mut self, name: &str) -> BuildError {
+        self.path.insert(0, name.to_string());
+        self
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.error.kind()
+    }
+
+    /// The member and option names leading to the field class, outermost first
+    pub fn path(&self) -> &[String] {
+        &self.path
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path.join("."), self.message)
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+impl From<Error> for BuildError {
+    fn from(error: Error) -> BuildError {
+        BuildError {
+            error,
+            path: Vec::new(),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl From<BuildError> for Error {
+    fn from(error: BuildError) -> Error {
+        error.error
+    }
+}
+
+pub type BuildResult<T> = std::result::Result<T, BuildError>;
+
+/// A structure member created so far, to look up length and selector fields
+struct Named {
+    name: String,
+    field_class: BtFieldClass,
+    /// The arrays, options and variant options containing the member
+    scope: Vec<usize>,
+}
+
+struct Builder<'a> {
+    trace_class: &'a BtTraceClass,
+    named: Vec<Named>,
+    /// The arrays, options and variant options containing the field class being built
+    scope: Vec<usize>,
+    next_scope: usize,
+}
+
+fn created(field_class: BtFieldClassOwned) -> Result<BtFieldClassOwned> {
+    if field_class.is_empty() {
+        Err(Error::new(ErrorKind::MemoryError, "BtFieldClass"))
+    } else {
+        Ok(field_class)
+    }
+}
+
+fn unsigned_range_set(ranges: &[RangeInclusive<u64>]) -> Result<BtIntegerRangeSetUnsignedOwned> {
+    let mut range_set = BtIntegerRangeSetUnsigned::create();
+    if range_set.is_empty() {
+        return Err(Error::new(
+            ErrorKind::MemoryError,
+            "BtIntegerRangeSetUnsigned",
+        ));
+    }
+    for range in ranges {
+        range_set.try_add_range(*range.start(), *range.end())?;
+    }
+    Ok(range_set)
+}
+
+fn signed_range_set(ranges: &[RangeInclusive<i64>]) -> Result<BtIntegerRangeSetSignedOwned> {
+    let mut range_set = BtIntegerRangeSetSigned::create();
+    if range_set.is_empty() {
+        return Err(Error::new(
+            ErrorKind::MemoryError,
+            "BtIntegerRangeSetSigned",
+        ));
+    }
+    for range in ranges {
+        range_set.try_add_range(*range.start(), *range.end())?;
+    }
+    Ok(range_set)
+}
+
+fn is_unsigned_integer(field_class_type: BtFieldClassType) -> bool {
+    matches!(
+        field_class_type,
+        BtFieldClassType::UnsignedInteger | BtFieldClassType::UnsignedEnumeration
+    )
+}
+
+fn is_signed_integer(field_class_type: BtFieldClassType) -> bool {
+    matches!(
+        field_class_type,
+        BtFieldClassType::SignedInteger | BtFieldClassType::SignedEnumeration
+    )
+}
+
+fn is_integer(field_class_type: BtFieldClassType) -> bool {
+    is_unsigned_integer(field_class_type) || is_signed_integer(field_class_type)
+}
+
+fn is_bool(field_class_type: BtFieldClassType) -> bool {
+    field_class_type == BtFieldClassType::Bool
+}
+
+impl<'a> Builder<'a> {
+    /// Look up the member `name` for the field class being built. Fails with
+    /// [`ErrorKind::UserError`] if the name is ambiguous, if the member is in an array, option
+    /// or variant option not containing the field class being built, or if `is_valid` rejects
+    /// its type.
+    fn lookup(
+        &self,
+        name: &str,
+        is_valid: fn(BtFieldClassType) -> bool,
+    ) -> BuildResult<BtFieldClass> {
+        let mut found = None;
+        for named in self.named.iter().filter(|named| named.name == name) {
+            if found.is_some() {
+                let message = format!("The name of the field `{name}` is not unique");
+                return Err(BuildError::new(ErrorKind::UserError, message));
+            }
+            if !self.scope.starts_with(&named.scope) {
+                let message = format!(
+                    "The field `{name}` is in an array, option or variant option not containing \
+                     this field class"
+                );
+                return Err(BuildError::new(ErrorKind::UserError, message));
+            }
+            found = Some(&named.field_class);
+        }
+        let Some(field_class) = found else {
+            let message = format!("No field `{name}` was created before this field class");
+            return Err(BuildError::new(ErrorKind::NotFound, message));
+        };
+        let field_class_type = field_class.get_type();
+        if !is_valid(field_class_type) {
+            let message =
+                format!("The field `{name}` can't be used with its type {field_class_type:?}");
+            return Err(BuildError::new(ErrorKind::UserError, message));
+        }
+        Ok(BtFieldClass::from_ptr(field_class.as_ptr()))
+    }
+
+    fn lookup_optional(
+        &self,
+        name: &Option<String>,
+        is_valid: fn(BtFieldClassType) -> bool,
+    ) -> BuildResult<BtFieldClass> {
+        match name {
+            Some(name) => self.lookup(name, is_valid),
+            None => Ok(BtFieldClass::empty()),
+        }
+    }
+
+    fn leaf(field_class: BtFieldClassOwned) -> BuildResult<FieldClassHandle> {
+        Ok(FieldClassHandle {
+            field_class: created(field_class)?,
+            children: Vec::new(),
+        })
+    }
+
+    /// Build the element of an array, the field class of an option or a variant option, named
+    /// `name` in the path of the errors. The members created inside can't be looked up from
+    /// outside.
+    fn build_contained(
+        &mut self,
+        name: &str,
+        spec: &FieldClassSpec,
+    ) -> BuildResult<FieldClassHandle> {
+        self.scope.push(self.next_scope);
+        self.next_scope += 1;
+        let handle = self.build(spec);
+        self.scope.pop();
+        handle.map_err(|error| error.in_name(name))
+    }
+
+    fn build(&mut self, spec: &FieldClassSpec) -> BuildResult<FieldClassHandle> {
+        let trace_class = self.trace_class;
+        match spec {
+            FieldClassSpec::Bool => Self::leaf(BtFieldClass::bool_create(trace_class)),
+            FieldClassSpec::BitArray { length } => {
+                if !(1..=64).contains(length) {
+                    let message = format!("The length {length} of the bit array is not 1 to 64");
+                    return Err(BuildError::new(ErrorKind::UserError, message));
+                }
+                Self::leaf(BtFieldClass::bit_array_create(trace_class, *length))
+            }
+            FieldClassSpec::UnsignedInteger(integer) => {
+                Self::integer(BtFieldClass::integer_unsigned_create(trace_class), integer)
+            }
+            FieldClassSpec::SignedInteger(integer) => {
+                Self::integer(BtFieldClass::integer_signed_create(trace_class), integer)
+            }
+            FieldClassSpec::SinglePrecisionReal => {
+                Self::leaf(BtFieldClass::real_single_precision_create(trace_class))
+            }
+            FieldClassSpec::DoublePrecisionReal => {
+                Self::leaf(BtFieldClass::real_double_precision_create(trace_class))
+            }
+            FieldClassSpec::String => Self::leaf(BtFieldClass::string_create(trace_class)),
+            FieldClassSpec::Enumeration {
+                integer,
+                mappings,
+                signed,
+            } => {
+                let field_class = if *signed {
+                    BtFieldClass::enumeration_signed_create(trace_class)
+                } else {
+                    BtFieldClass::enumeration_unsigned_create(trace_class)
+                };
+                let mut handle = Self::integer(field_class, integer)?;
+                for (label, ranges) in mappings {
+                    match ranges {
+                        Ranges::Unsigned(ranges) if !*signed => {
+                            handle.field_class.try_enumeration_unsigned_add_mapping(
+                                label,
+                                &unsigned_range_set(ranges)?.as_const(),
+                            )?
+                        }
+                        Ranges::Signed(ranges) if *signed => {
+                            handle.field_class.try_enumeration_signed_add_mapping(
+                                label,
+                                &signed_range_set(ranges)?.as_const(),
+                            )?
+                        }
+                        _ => {
+                            let message = format!(
+                                "The ranges of the mapping `{label}` don't have the signedness of \
+                                 the enumeration"
+                            );
+                            return Err(BuildError::new(ErrorKind::UserError, message));
+                        }
+                    }
+                }
+                Ok(handle)
+            }
+            FieldClassSpec::StaticArray { element, length } => {
+                let element = self.build_contained("[]", element)?;
+                let field_class = BtFieldClass::array_static_create(trace_class, &element, *length);
+                Ok(FieldClassHandle {
+                    field_class: created(field_class)?,
+                    children: vec![(String::new(), element)],
+                })
+            }
+            FieldClassSpec::DynamicArray {
+                element,
+                length_field,
+            } => {
+                let length_field = self.lookup_optional(length_field, is_unsigned_integer)?;
+                let element = self.build_contained("[]", element)?;
+                let field_class =
+                    BtFieldClass::array_dynamic_create(trace_class, &element, &length_field);
+                Ok(FieldClassHandle {
+                    field_class: created(field_class)?,
+                    children: vec![(String::new(), element)],
+                })
+            }
+            FieldClassSpec::Option {
+                field_class,
+                selector,
+            } => {
+                let selector_field = match selector {
+                    OptionSelector::None => BtFieldClass::empty(),
+                    OptionSelector::Bool { field, .. } => self.lookup(field, is_bool)?,
+                    OptionSelector::Integer {
+                        field,
+                        ranges: Ranges::Unsigned(_),
+                    } => self.lookup(field, is_unsigned_integer)?,
+                    OptionSelector::Integer {
+                        field,
+                        ranges: Ranges::Signed(_),
+                    } => self.lookup(field, is_signed_integer)?,
+                };
+                let optional = self.build_contained("?", field_class)?;
+                let field_class = match selector {
+                    OptionSelector::None => {
+                        BtFieldClass::option_without_selector_create(trace_class, &optional)
+                    }
+                    OptionSelector::Bool { reversed, .. } => {
+                        let mut field_class =
+                            created(BtFieldClass::option_with_selector_field_bool_create(
+                                trace_class,
+                                &optional,
+                                &selector_field,
+                            ))?;
+                        field_class
+                            .option_with_selector_field_bool_set_selector_is_reversed(*reversed);
+                        field_class
+                    }
+                    OptionSelector::Integer {
+                        ranges: Ranges::Unsigned(ranges),
+                        ..
+                    } => BtFieldClass::option_with_selector_field_integer_unsigned_create(
+                        trace_class,
+                        &optional,
+                        &selector_field,
+                        &unsigned_range_set(ranges)?.as_const(),
+                    ),
+                    OptionSelector::Integer {
+                        ranges: Ranges::Signed(ranges),
+                        ..
+                    } => BtFieldClass::option_with_selector_field_integer_signed_create(
+                        trace_class,
+                        &optional,
+                        &selector_field,
+                        &signed_range_set(ranges)?.as_const(),
+                    ),
+                };
+                Ok(FieldClassHandle {
+                    field_class: created(field_class)?,
+                    children: vec![(String::new(), optional)],
+                })
+            }
+            FieldClassSpec::Variant {
+                options,
+                selector_field,
+            } => {
+                let selector = self.lookup_optional(selector_field, is_integer)?;
+                // The ranges of the options have to match the selector field
+                let signed = !selector.is_empty() && is_signed_integer(selector.get_type());
+                let mismatch = options.iter().find(|option| match &option.ranges {
+                    None => !selector.is_empty(),
+                    Some(Ranges::Unsigned(_)) => selector.is_empty() || signed,
+                    Some(Ranges::Signed(_)) => !signed,
+                });
+                if let Some(option) = mismatch {
+                    let message = format!(
+                        "The ranges of the option `{}` don't match the selector field",
+                        option.name
+                    );
+                    return Err(BuildError::new(ErrorKind::UserError, message));
+                }
+                let mut field_class =
+                    created(BtFieldClass::variant_create(trace_class, &selector))?;
+                let mut children = Vec::new();
+                for option in options {
+                    let option_class = self.build_contained(&option.name, &option.field_class)?;
+                    match &option.ranges {
+                        None => field_class.try_variant_without_selector_append_option(
+                            &option.name,
+                            &option_class,
+                        )?,
+                        Some(Ranges::Unsigned(ranges)) => field_class
+                            .try_variant_with_selector_field_integer_unsigned_append_option(
+                                &option.name,
+                                &option_class,
+                                &unsigned_range_set(ranges)?.as_const(),
+                            )?,
+                        Some(Ranges::Signed(ranges)) => field_class
+                            .try_variant_with_selector_field_integer_signed_append_option(
+                                &option.name,
+                                &option_class,
+                                &signed_range_set(ranges)?.as_const(),
+                            )?,
+                    }
+                    children.push((option.name.clone(), option_class));
+                }
+                Ok(FieldClassHandle {
+                    field_class,
+                    children,
+                })
+            }
+            FieldClassSpec::Structure(members) => {
+                let mut field_class = created(BtFieldClass::structure_create(trace_class))?;
+                let mut children = Vec::new();
+                for (name, member) in members {
+                    let member_class = self.build(member).map_err(|error| error.in_name(name))?;
+                    field_class.try_structure_append_member(name, &member_class)?;
+                    self.named.push(Named {
+                        name: name.clone(),
+                        field_class: BtFieldClass::from_ptr(member_class.as_ptr()),
+                        scope: self.scope.clone(),
+                    });
+                    children.push((name.clone(), member_class));
+                }
+                Ok(FieldClassHandle {
+                    field_class,
+                    children,
+                })
+            }
+        }
+    }
+
+    /// Fails with [`ErrorKind::UserError`] if the field value range isn't 1 to 64
+    fn integer(
+        field_class: BtFieldClassOwned,
+        integer: &IntegerSpec,
+    ) -> BuildResult<FieldClassHandle> {
+        let field_value_range = integer.field_value_range;
+        if !(1..=64).contains(&field_value_range) {
+            let message = format!("The field value range {field_value_range} is not 1 to 64");
+            return Err(BuildError::new(ErrorKind::UserError, message));
+        }
+        let mut field_class = created(field_class)?;
+        field_class.integer_set_field_value_range(integer.field_value_range);
+        field_class.integer_set_preferred_display_base(integer.display_base);
+        Ok(FieldClassHandle {
+            field_class,
+            children: Vec::new(),
+        })
+    }
+}
@@ -0,0 +1,146 @@
+// Copyright 2023 - 2023, Karsten Becker and the rust babeltrace2-plugin contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+//! An owned copy of a field of any class
+//!
+//! [`FieldValue::from_bt_field`] walks a field and everything it contains, so generic tools
+//! like dumpers or comparers work on plain Rust values instead of borrowing fields. With the
+//! `serde` feature, it is serialized as described in the `ser` module.
+use crate::bt2::*;
+use crate::error::{Error, ErrorKind, Result};
+use crate::payload::FromBtField;
+
+/// The value of a field, mirroring its class type
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Bool(bool),
+    BitArray {
+        value: u64,
+        length: u64,
+    },
+    UInt(u64),
+    SInt(i64),
+    /// A single or double precision real
+    Real(f64),
+    /// Invalid UTF-8 sequences are replaced
+    String(String),
+    /// An enumeration, `value` is an `UInt` or `SInt` and `labels` are the ones of the mappings
+    /// containing it
+    Enum {
+        value: Box<FieldValue>,
+        labels: Vec<String>,
+    },
+    /// A static or dynamic array
+    Array(Vec<FieldValue>),
+    Struct(Vec<(String, FieldValue)>),
+    Option(Option<Box<FieldValue>>),
+    Variant {
+        option_name: String,
+        value: Box<FieldValue>,
+    },
+}
+
+fn to_strings(labels: Vec<&std::ffi::CStr>) -> Vec<String> {
+    labels
+        .into_iter()
+        .map(|label| label.to_string_lossy().into_owned())
+        .collect()
+}
+
+impl FromBtField for FieldValue {
+    fn from_bt_field(field: &BtFieldConst) -> Result<Self> {
+        Ok(match field.get_class_type() {
+            BtFieldClassType::Bool => FieldValue::Bool(field.bool_get_value()),
+            BtFieldClassType::BitArray => FieldValue::BitArray {
+                value: field.bit_array_get_value_as_integer(),
+                length: field.borrow_class_const().bit_array_get_length(),
+            },
+            BtFieldClassType::UnsignedInteger => {
+                FieldValue::UInt(field.integer_unsigned_get_value())
+            }
+            BtFieldClassType::SignedInteger => FieldValue::SInt(field.integer_signed_get_value()),
+            BtFieldClassType::UnsignedEnumeration => FieldValue::Enum {
+                value: Box::new(FieldValue::UInt(field.integer_unsigned_get_value())),
+                labels: to_strings(field.try_enumeration_unsigned_get_mapping_labels()?),
+            },
+            BtFieldClassType::SignedEnumeration => FieldValue::Enum {
+                value: Box::new(FieldValue::SInt(field.integer_signed_get_value())),
+                labels: to_strings(field.try_enumeration_signed_get_mapping_labels()?),
+            },
+            BtFieldClassType::SinglePrecisionReal => {
+                FieldValue::Real(field.real_single_precision_get_value().into())
+            }
+            BtFieldClassType::DoublePrecisionReal => {
+                FieldValue::Real(field.real_double_precision_get_value())
+            }
+            BtFieldClassType::String => {
+                FieldValue::String(field.string_get_value().to_string_lossy().into_owned())
+            }
+            BtFieldClassType::Structure => {
+                let class = field.borrow_class_const();
+                let members = (0..class.structure_get_member_count())
+                    .map(|index| {
+                        let name = class
+                            .structure_borrow_member_by_index_const(index)
+                            .get_name()
+                            .to_string_lossy()
+                            .into_owned();
+                        let value = FieldValue::from_bt_field(
+                            &field.structure_borrow_member_field_by_index_const(index),
+                        )?;
+                        Ok((name, value))
+                    })
+                    .collect::<Result<_>>()?;
+                FieldValue::Struct(members)
+            }
+            BtFieldClassType::StaticArray
+            | BtFieldClassType::DynamicArrayWithoutLengthField
+            | BtFieldClassType::DynamicArrayWithLengthField => {
+                let elements = (0..field.array_get_length())
+                    .map(|index| {
+                        FieldValue::from_bt_field(
+                            &field.array_borrow_element_field_by_index_const(index),
+                        )
+                    })
+                    .collect::<Result<_>>()?;
+                FieldValue::Array(elements)
+            }
+            BtFieldClassType::OptionWithoutSelectorField
+            | BtFieldClassType::OptionWithBoolSelectorField
+            | BtFieldClassType::OptionWithUnsignedIntegerSelectorField
+            | BtFieldClassType::OptionWithSignedIntegerSelectorField => {
+                let option = field.option_borrow_field_const();
+                if option.as_ptr().is_null() {
+                    FieldValue::Option(None)
+                } else {
+                    FieldValue::Option(Some(Box::new(FieldValue::from_bt_field(&option)?)))
+                }
+            }
+            BtFieldClassType::VariantWithoutSelectorField
+            | BtFieldClassType::VariantWithUnsignedIntegerSelectorField
+            | BtFieldClassType::VariantWithSignedIntegerSelectorField => FieldValue::Variant {
+                option_name: field
+                    .variant_borrow_selected_option_class_const()
+                    .get_name()
+                    .to_string_lossy()
+                    .into_owned(),
+                value: Box::new(FieldValue::from_bt_field(
+                    &field.variant_borrow_selected_option_field_const(),
+                )?),
+            },
+            _ => return Err(Error::new(ErrorKind::TypeMismatch, "FieldValue")),
+        })
+    }
+}
+
+impl FieldValue {
+    /// The member `name` of a `Struct`
+    pub fn member(&self, name: &str) -> Option<&FieldValue> {
+        match self {
+            FieldValue::Struct(members) => members
+                .iter()
+                .find(|(member_name, _)| member_name == name)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,202 @@
+// Copyright 2023 - 2023, Karsten Becker and the rust babeltrace2-plugin contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+//! Trait based filter component classes
+//!
+//! Implement [`FilterComponent`] and either create the component class at runtime with
+//! [`create_component_class`], or reference the trampolines of this module (for example
+//! `filter::initialize::<MyFilter>`) in [`bt_plugin!`](crate::bt_plugin). The trampolines
+//! are only meant to be called by libbabeltrace2.
+#![allow(clippy::missing_safety_doc)]
+use std::ffi::{c_void, CStr};
+use std::os::raw::c_char;
+
+use crate::bt2::binding::*;
+use crate::bt2::*;
+use crate::component;
+use crate::error::{Error, ErrorKind};
+use crate::params::{set_help, ParamSchema};
+
+/// The methods of a filter component class. The implementing type is the private data of
+/// each component instance, it is created by [`FilterComponent::initialize`] and dropped
+/// after [`FilterComponent::finalize`].
+pub trait FilterComponent: Sized {
+    /// The initialization parameters, which are validated before [`initialize`](Self::initialize)
+    /// if given. See [`params`](crate::params).
+    const PARAMS: Option<ParamSchema> = None;
+
+    /// Called when a component is instantiated. This typically adds the input and output ports.
+    fn initialize(
+        self_component: &mut BtSelfComponentFilter,
+        configuration: &mut BtSelfComponentFilterConfiguration,
+        params: &BtValueConst,
+    ) -> Result<Self, BtComponentClassInitializeMethodStatus>;
+
+    /// Called before the component is destroyed.
+    fn finalize(&mut self, _self_component: &mut BtSelfComponentFilter) {}
+
+    /// Called when one of the input ports is connected to an output port.
+    fn input_port_connected(
+        &mut self,
+        _self_component: &mut BtSelfComponentFilter,
+        _self_port: &mut BtSelfComponentPortInput,
+        _other_port: &BtPortOutputConst,
+    ) -> BtComponentClassPortConnectedMethodStatus {
+        BtComponentClassPortConnectedMethodStatus::Ok
+    }
+
+    /// Called when one of the output ports is connected to an input port.
+    fn output_port_connected(
+        &mut self,
+        _self_component: &mut BtSelfComponentFilter,
+        _self_port: &mut BtSelfComponentPortOutput,
+        _other_port: &BtPortInputConst,
+    ) -> BtComponentClassPortConnectedMethodStatus {
+        BtComponentClassPortConnectedMethodStatus::Ok
+    }
+
+    /// Called to query the object `object_name`. The reference of the returned value is
+    /// passed to the caller.
+    fn query(
+        _self_component_class: &mut BtSelfComponentClassFilter,
+        _query_executor: &mut BtPrivateQueryExecutor,
+        _object_name: &CStr,
+        _params: &BtValueConst,
+    ) -> Result<BtValueConstOwned, BtComponentClassQueryMethodStatus> {
+        Err(BtComponentClassQueryMethodStatus::UnknownObject)
+    }
+
+    /// Called to get the supported message interchange protocol versions. Only version 0 is
+    /// supported by default, just like without this method.
+    fn get_supported_mip_versions(
+        _self_component_class: &mut BtSelfComponentClassFilter,
+        _params: &BtValueConst,
+        _logging_level: BtLoggingLevel,
+        supported_versions: &mut BtIntegerRangeSetUnsigned,
+    ) -> BtComponentClassGetSupportedMipVersionsMethodStatus {
+        component::mip_version_0(supported_versions)
+    }
+}
+
+/// Creates a filter component class named `name` with the methods of `T` and the message
+/// iterator class `message_iterator_class`. Fails if `name` contains a NUL character.
+pub fn create_component_class<T: FilterComponent>(
+    name: &str,
+    message_iterator_class: &BtMessageIteratorClass,
+) -> crate::error::Result<BtComponentClassFilterOwned> {
+    let mut component_class = BtComponentClassFilter::create(name, message_iterator_class)?;
+    if component_class.is_empty() {
+        return Err(Error::new(ErrorKind::MemoryError, "BtComponentClassFilter"));
+    }
+    component_class.set_initialize_method(Some(initialize::<T>));
+    component_class.set_finalize_method(Some(finalize::<T>));
+    component_class.set_input_port_connected_method(Some(input_port_connected::<T>));
+    component_class.set_output_port_connected_method(Some(output_port_connected::<T>));
+    component_class.set_query_method(Some(query::<T>));
+    component_class.set_get_supported_mip_versions_method(Some(get_supported_mip_versions::<T>));
+    if let Some(schema) = T::PARAMS {
+        set_help(&mut component_class.as_component_class_inline(), &schema)?;
+    }
+    Ok(component_class)
+}
+
+/// Trampoline for [`FilterComponent::initialize`]
+pub unsafe extern "C" fn initialize<T: FilterComponent>(
+    self_component: *mut bt_self_component_filter,
+    configuration: *mut bt_self_component_filter_configuration,
+    params: *const bt_value,
+    _initialize_method_data: *mut c_void,
+) -> bt_component_class_initialize_method_status {
+    let mut self_component = BtSelfComponentFilter::from_ptr(self_component);
+    let mut configuration = BtSelfComponentFilterConfiguration::from_ptr(configuration);
+    component::initialize(
+        &mut self_component,
+        T::PARAMS,
+        params,
+        |self_component, params| T::initialize(self_component, &mut configuration, params),
+        |data| data,
+    )
+}
+
+/// Trampoline for [`FilterComponent::finalize`]
+pub unsafe extern "C" fn finalize<T: FilterComponent>(
+    self_component: *mut bt_self_component_filter,
+) {
+    let mut self_component = BtSelfComponentFilter::from_ptr(self_component);
+    component::finalize(&mut self_component, T::finalize);
+}
+
+/// Trampoline for [`FilterComponent::input_port_connected`]
+pub unsafe extern "C" fn input_port_connected<T: FilterComponent>(
+    self_component: *mut bt_self_component_filter,
+    self_port: *mut bt_self_component_port_input,
+    other_port: *const bt_port_output,
+) -> bt_component_class_port_connected_method_status {
+    let mut self_component = BtSelfComponentFilter::from_ptr(self_component);
+    let mut self_port = BtSelfComponentPortInput::from_ptr(self_port);
+    let other_port = BtPortOutputConst::from_ptr(other_port);
+    component::with_data(
+        &mut self_component,
+        BtComponentClassPortConnectedMethodStatus::Error,
+        |data: &mut T, self_component| {
+            data.input_port_connected(self_component, &mut self_port, &other_port)
+        },
+    )
+    .into()
+}
+
+/// Trampoline for [`FilterComponent::output_port_connected`]
+pub unsafe extern "C" fn output_port_connected<T: FilterComponent>(
+    self_component: *mut bt_self_component_filter,
+    self_port: *mut bt_self_component_port_output,
+    other_port: *const bt_port_input,
+) -> bt_component_class_port_connected_method_status {
+    let mut self_component = BtSelfComponentFilter::from_ptr(self_component);
+    let mut self_port = BtSelfComponentPortOutput::from_ptr(self_port);
+    let other_port = BtPortInputConst::from_ptr(other_port);
+    component::with_data(
+        &mut self_component,
+        BtComponentClassPortConnectedMethodStatus::Error,
+        |data: &mut T, self_component| {
+            data.output_port_connected(self_component, &mut self_port, &other_port)
+        },
+    )
+    .into()
+}
+
+/// Trampoline for [`FilterComponent::query`]
+pub unsafe extern "C" fn query<T: FilterComponent>(
+    self_component_class: *mut bt_self_component_class_filter,
+    query_executor: *mut bt_private_query_executor,
+    object_name: *const c_char,
+    params: *const bt_value,
+    _method_data: *mut c_void,
+    result: *mut *const bt_value,
+) -> bt_component_class_query_method_status {
+    let mut self_component_class = BtSelfComponentClassFilter::from_ptr(self_component_class);
+    component::query(
+        &mut self_component_class,
+        query_executor,
+        object_name,
+        params,
+        result,
+        T::query,
+    )
+}
+
+/// Trampoline for [`FilterComponent::get_supported_mip_versions`]
+pub unsafe extern "C" fn get_supported_mip_versions<T: FilterComponent>(
+    self_component_class: *mut bt_self_component_class_filter,
+    params: *const bt_value,
+    _method_data: *mut c_void,
+    logging_level: bt_logging_level,
+    supported_versions: *mut bt_integer_range_set_unsigned,
+) -> bt_component_class_get_supported_mip_versions_method_status {
+    let mut self_component_class = BtSelfComponentClassFilter::from_ptr(self_component_class);
+    component::get_supported_mip_versions(
+        &mut self_component_class,
+        params,
+        logging_level,
+        supported_versions,
+        T::get_supported_mip_versions,
+    )
+}
@@ -1,8 +1,32 @@
 // Copyright 2023 - 2023, Karsten Becker and the rust babeltrace2-plugin contributors
 // SPDX-License-Identifier: GPL-2.0-or-later
 pub mod bt2;
+mod component;
+pub mod current_thread;
+pub mod error;
+pub mod field_class;
+pub mod field_value;
+pub mod filter;
+pub mod message;
+pub mod message_iterator;
+pub mod muxer;
+pub mod params;
+pub mod payload;
+pub mod plugin;
+#[cfg(feature = "serde")]
+pub mod ser;
+#[cfg(feature = "serde")]
+pub mod serde_value;
+pub mod sink;
+pub mod source;
+pub mod stream_writer;
+pub mod upstream;
+pub mod value;
+
+// The derive macros refer to this crate by name
+extern crate self as babeltrace2_plugin;
+
 use std::ffi::CStr;
-use std::mem::MaybeUninit;
 
 use bt2::binding::*;
 use bt2::*;
@@ -45,22 +69,10 @@ impl BtVersion {
 pub fn iterator_to_vec(
     iter: &mut BtMessageIterator,
 ) -> Result<Vec<BtMessageConst>, BtMessageIteratorNextStatus> {
-    /* Consume a batch of messages from the upstream message iterator */
-    let mut messages: MaybeUninit<*mut *const bt_message> = MaybeUninit::uninit();
-    let mut count: u64 = 0;
-    let next_status = unsafe {iter.next(messages.as_mut_ptr(), &mut count)};
-
-    if next_status != BtMessageIteratorNextStatus::Ok {
-        return Err(next_status);
-    }
-    let msg_out: *mut *const bt_message = unsafe { *messages.as_mut_ptr() };
-    let msgs: &[*const bt_message] = unsafe { std::slice::from_raw_parts(msg_out, count as usize) };
-    /* For each consumed message */
-    let mut result = Vec::new();
-    for msg in msgs {
-        result.push(BtMessageConst::from_ptr(*msg));
-    }
-    Ok(result)
+    /* Consume a batch of messages from the upstream message iterator, the references are
+     * passed to the caller */
+    let messages = iter.next()?;
+    Ok(messages.into_iter().map(|msg| msg.into_inner()).collect())
 }
 
 impl From<BtMessageIteratorNextStatus> for BtComponentClassSinkConsumeMethodStatus {
@@ -77,6 +89,34 @@ impl From<BtMessageIteratorNextStatus> for BtComponentClassSinkConsumeMethodStat
     }
 }
 
+impl From<BtMessageIteratorCreateFromSinkComponentStatus> for BtComponentClassSinkGraphIsConfiguredMethodStatus {
+    fn from(item: BtMessageIteratorCreateFromSinkComponentStatus) -> Self {
+        match item {
+            BtMessageIteratorCreateFromSinkComponentStatus::Ok => BtComponentClassSinkGraphIsConfiguredMethodStatus::Ok,
+            BtMessageIteratorCreateFromSinkComponentStatus::MemoryError => {
+                BtComponentClassSinkGraphIsConfiguredMethodStatus::MemoryError
+            }
+            BtMessageIteratorCreateFromSinkComponentStatus::Error => {
+                BtComponentClassSinkGraphIsConfiguredMethodStatus::Error
+            }
+        }
+    }
+}
+
+impl From<BtMessageIteratorCreateFromMessageIteratorStatus> for BtMessageIteratorClassInitializeMethodStatus {
+    fn from(item: BtMessageIteratorCreateFromMessageIteratorStatus) -> Self {
+        match item {
+            BtMessageIteratorCreateFromMessageIteratorStatus::Ok => BtMessageIteratorClassInitializeMethodStatus::Ok,
+            BtMessageIteratorCreateFromMessageIteratorStatus::MemoryError => {
+                BtMessageIteratorClassInitializeMethodStatus::MemoryError
+            }
+            BtMessageIteratorCreateFromMessageIteratorStatus::Error => {
+                BtMessageIteratorClassInitializeMethodStatus::Error
+            }
+        }
+    }
+}
+
 pub trait ToSelfComponent {
     fn to_self_component(&mut self) -> BtSelfComponent;
 }
@@ -107,6 +147,14 @@ pub fn get_boxed_data<T>(comp: &mut dyn ToSelfComponent) -> Box<T> {
     unsafe { Box::from_raw(comp.to_self_component().get_data() as *mut T) }
 }
 
+/// Borrow the data set with [`set_boxed_data`] without taking ownership of it.
+///
+/// # Safety
+/// The data has to be of type `T` and must not be dropped while the reference is alive.
+pub unsafe fn borrow_boxed_data<'a, T>(comp: &mut dyn ToSelfComponent) -> &'a mut T {
+    &mut *(comp.to_self_component().get_data() as *mut T)
+}
+
 pub fn get_scoped_boxed_data<T, F, R>(comp: &mut dyn ToSelfComponent, mut scope: F) -> R
 where
     F: FnMut(&mut Box<T>) -> R,
@@ -117,11 +165,12 @@ where
     res
 }
 
-pub fn get_iterator(comp: &BtSelfComponentSink, in_port: &BtSelfComponentPortInput) -> BtMessageIterator{
-    let mut iter: MaybeUninit<*mut bt_message_iterator> = MaybeUninit::uninit();
-    unsafe {
-    BtMessageIterator::create_from_sink_component(comp, in_port, iter.as_mut_ptr());
-    BtMessageIterator::from_ptr(*iter.as_mut_ptr())}
+/// Create a message iterator on the upstream component connected to `in_port`.
+pub fn get_iterator(
+    comp: &BtSelfComponentSink,
+    in_port: &BtSelfComponentPortInput,
+) -> Result<BtMessageIteratorOwned, BtMessageIteratorCreateFromSinkComponentStatus> {
+    BtMessageIterator::create_from_sink_component(comp, in_port)
 }
 
 pub fn drop_data(comp: &mut dyn ToSelfComponent){
@@ -158,4 +207,352 @@ mod tests {
         assert_eq!(to_camel_case("aBCD_efgh"), "AbcdEfgh");
         assert_eq!(to_camel_case("abcd_efgh_ijkl"), "AbcdEfghIjkl");
     }
+
+    #[test]
+    fn status_into_result() {
+        use crate::bt2::BtGraphRunStatus;
+        use crate::error::ErrorKind;
+        assert_eq!(BtGraphRunStatus::Ok.into_result(), Ok(()));
+        let err = BtGraphRunStatus::Again.into_result().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Again);
+        assert_eq!(err.to_string(), "BtGraphRunStatus::Again");
+    }
+
+    #[test]
+    fn as_c_str() {
+        use crate::bt2::AsCStr;
+        assert_eq!("trace".as_c_str().unwrap().to_bytes_with_nul(), b"trace\0");
+        let long = "x".repeat(200);
+        assert_eq!(long.as_c_str().unwrap().to_bytes(), long.as_bytes());
+        assert!("tr\0ace".as_c_str().is_err());
+    }
+
+    #[test]
+    fn field_class_spec_builder() {
+        use crate::bt2::BtFieldClassIntegerPreferredDisplayBase;
+        use crate::field_class::{FieldClassSpec, IntegerSpec};
+        let spec = FieldClassSpec::structure()
+            .member("len", FieldClassSpec::unsigned_integer(8))
+            .member(
+                "data",
+                FieldClassSpec::dynamic_array(
+                    FieldClassSpec::unsigned_integer(8).display_base(BtFieldClassIntegerPreferredDisplayBase::Hexadecimal),
+                    Some("len"),
+                ),
+            );
+        let expected_element = FieldClassSpec::UnsignedInteger(IntegerSpec {
+            field_value_range: 8,
+            display_base: BtFieldClassIntegerPreferredDisplayBase::Hexadecimal,
+        });
+        match spec {
+            FieldClassSpec::Structure(members) => {
+                assert_eq!(members.len(), 2);
+                assert_eq!(members[1].1, FieldClassSpec::dynamic_array(expected_element, Some("len")));
+            }
+            _ => panic!("Expected a structure"),
+        }
+    }
+
+    #[test]
+    fn bt_payload_derive() {
+        use crate::bt2::BtFieldClassIntegerPreferredDisplayBase;
+        use crate::field_class::*;
+        use crate::payload::{BtPayload, FromBtField};
+
+        #[derive(BtPayload, FromBtField)]
+        #[allow(dead_code)]
+        enum Level {
+            Low = 2,
+            High,
+        }
+
+        #[derive(BtPayload, FromBtField)]
+        #[allow(dead_code)]
+        struct Sample {
+            #[bt(display_base = "hexadecimal")]
+            address: u32,
+            #[bt(rename = "level_of_sample")]
+            level: Level,
+            #[bt(skip)]
+            cache: u8,
+            names: Vec<String>,
+        }
+
+        let expected = FieldClassSpec::structure()
+            .member(
+                "address",
+                FieldClassSpec::unsigned_integer(32)
+                    .display_base(BtFieldClassIntegerPreferredDisplayBase::Hexadecimal),
+            )
+            .member(
+                "level_of_sample",
+                FieldClassSpec::signed_enumeration(64)
+                    .mapping("Low", Ranges::Signed(vec![2..=2]))
+                    .mapping("High", Ranges::Signed(vec![3..=3])),
+            )
+            .member("names", FieldClassSpec::dynamic_array(FieldClassSpec::String, None));
+        assert_eq!(Sample::field_class_spec(), expected);
+    }
+
+    #[test]
+    fn field_value_member() {
+        use crate::field_value::FieldValue;
+        let value = FieldValue::Struct(vec![
+            ("id".to_string(), FieldValue::UInt(3)),
+            ("name".to_string(), FieldValue::String("cpu".to_string())),
+        ]);
+        assert_eq!(value.member("name"), Some(&FieldValue::String("cpu".to_string())));
+        assert_eq!(value.member("other"), None);
+        assert_eq!(FieldValue::UInt(3).member("id"), None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn field_value_serialize() {
+        use crate::field_value::FieldValue;
+        use crate::serde_value::{from_value, to_value};
+        use serde::Deserialize;
+        use std::collections::HashMap;
+
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Level {
+            value: i64,
+            labels: Vec<String>,
+        }
+
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Sample {
+            id: u64,
+            level: Level,
+            data: HashMap<String, String>,
+            extra: Option<u8>,
+        }
+
+        let value = FieldValue::Struct(vec![
+            ("id".to_string(), FieldValue::UInt(3)),
+            (
+                "level".to_string(),
+                FieldValue::Enum {
+                    value: Box::new(FieldValue::SInt(-1)),
+                    labels: vec!["Low".to_string()],
+                },
+            ),
+            (
+                "data".to_string(),
+                FieldValue::Variant {
+                    option_name: "text".to_string(),
+                    value: Box::new(FieldValue::String("cpu".to_string())),
+                },
+            ),
+            ("extra".to_string(), FieldValue::Option(None)),
+        ]);
+        let expected = Sample {
+            id: 3,
+            level: Level {
+                value: -1,
+                labels: vec!["Low".to_string()],
+            },
+            data: HashMap::from([("text".to_string(), "cpu".to_string())]),
+            extra: None,
+        };
+        let value = to_value(&value).unwrap();
+        assert_eq!(from_value::<Sample>(&value.as_const()), Ok(expected));
+    }
+
+    #[test]
+    fn param_schema_help() {
+        use crate::bt2::BtValueType;
+        use crate::params::{Param, ParamDefault, ParamSchema};
+        const SCHEMA: ParamSchema = ParamSchema::new(
+            "Read samples",
+            &[
+                Param::required("path", BtValueType::String, "The file to read"),
+                Param::optional("count", BtValueType::UnsignedInteger, "The samples to read")
+                    .default(ParamDefault::UnsignedInteger(10)),
+            ],
+        );
+        assert_eq!(
+            SCHEMA.help(),
+            "Parameters:\n\n  path (string, required)\n    The file to read\n\n  \
+             count (unsigned integer, default: 10)\n    The samples to read\n"
+        );
+    }
+
+    #[test]
+    fn muxer_heads_order() {
+        use crate::muxer::Heads;
+        let mut heads = Heads::new();
+        heads.push(0, 20, "a");
+        heads.push(1, 10, "b");
+        heads.push(2, 20, "c");
+        heads.push(1, i64::MIN, "d");
+        let order: Vec<_> = std::iter::from_fn(|| heads.pop()).collect();
+        assert_eq!(order, vec![(1, "d"), (1, "b"), (0, "a"), (2, "c")]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_value_round_trip() {
+        use crate::serde_value::{from_value, to_value};
+        use serde::{Deserialize, Serialize};
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        enum Mode {
+            Fast,
+            Every(u32),
+        }
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Params {
+            path: String,
+            offset: i64,
+            scale: Option<f64>,
+            modes: Vec<Mode>,
+            pair: (bool, u8),
+        }
+
+        let params = Params {
+            path: "trace".to_string(),
+            offset: -2,
+            scale: None,
+            modes: vec![Mode::Fast, Mode::Every(3)],
+            pair: (true, 7),
+        };
+        let value = to_value(&params).unwrap();
+        assert_eq!(from_value::<Params>(&value.as_const()), Ok(params));
+        let err = from_value::<Vec<u8>>(&value.as_const()).unwrap_err();
+        assert!(err.path().is_empty());
+    }
+
+    #[test]
+    fn value_conversions() {
+        use crate::bt2::BtValueOwned;
+        use crate::error::ErrorKind;
+        use std::collections::HashMap;
+        let value = BtValueOwned::try_from(-3i64).unwrap();
+        assert_eq!(i64::try_from(&value.as_const()), Ok(-3));
+        let err = u64::try_from(&value.as_const()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::OverflowError);
+        let err = bool::try_from(&value.as_const()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TypeMismatch);
+        assert_eq!(f64::try_from(&BtValueOwned::try_from(1.5).unwrap().as_const()), Ok(1.5));
+
+        let value = BtValueOwned::try_from(vec!["a", "b"]).unwrap();
+        let strings = Vec::<String>::try_from(&value.as_const()).unwrap();
+        assert_eq!(strings, vec!["a".to_string(), "b".to_string()]);
+        let result = BtValueOwned::try_from("a\0b");
+        assert!(matches!(result, Err(err) if err.kind() == ErrorKind::InteriorNul));
+
+        let map = HashMap::from([("x".to_string(), 1u64), ("y".to_string(), 2)]);
+        let value = BtValueOwned::try_from(map.clone()).unwrap();
+        let value = value.as_const();
+        assert_eq!(HashMap::<String, u64>::try_from(&value), Ok(map));
+        let mut entries: Vec<_> = value
+            .map_entries()
+            .map(|(key, entry)| (key.to_str().unwrap(), u64::try_from(&*entry).unwrap()))
+            .collect();
+        entries.sort();
+        assert_eq!(entries, vec![("x", 1), ("y", 2)]);
+    }
+
+    #[test]
+    fn value_map_try_for_each_entry() {
+        use crate::bt2::BtValueOwned;
+        use std::collections::HashMap;
+        use std::panic::{self, AssertUnwindSafe};
+        let map = HashMap::from([("x".to_string(), 1u64), ("y".to_string(), 2)]);
+        let value = BtValueOwned::try_from(map).unwrap();
+        let value = value.as_const();
+
+        let mut calls = 0;
+        let result = value.map_try_for_each_entry(|key, _| {
+            calls += 1;
+            Err(key.to_owned())
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+
+        let panic = panic::catch_unwind(AssertUnwindSafe(|| {
+            value.map_try_for_each_entry(|_, _| -> Result<(), ()> { panic!("in entry") })
+        }))
+        .unwrap_err();
+        assert_eq!(panic.downcast_ref::<&str>(), Some(&"in entry"));
+    }
+
+    #[test]
+    fn message_builder_event_class_of_another_stream_class() {
+        use crate::bt2::*;
+        use crate::error::ErrorKind;
+        use crate::message::MessageBuilder;
+        use crate::message_iterator::{self, MessageBuffer, MessageIterator};
+        use crate::source::{self, SourceComponent};
+
+        struct Iterator;
+
+        impl MessageIterator for Iterator {
+            fn initialize(
+                _self_message_iterator: &mut BtSelfMessageIterator,
+                _configuration: &mut BtSelfMessageIteratorConfiguration,
+                _port: &mut BtSelfComponentPortOutput,
+            ) -> Result<Self, BtMessageIteratorClassInitializeMethodStatus> {
+                Ok(Iterator)
+            }
+
+            fn next(
+                &mut self,
+                _self_message_iterator: &mut BtSelfMessageIterator,
+                _messages: &mut MessageBuffer,
+            ) -> BtMessageIteratorClassNextMethodStatus {
+                BtMessageIteratorClassNextMethodStatus::End
+            }
+        }
+
+        struct Source;
+
+        impl SourceComponent for Source {
+            fn initialize(
+                self_component: &mut BtSelfComponentSource,
+                _configuration: &mut BtSelfComponentSourceConfiguration,
+                _params: &BtValueConst,
+            ) -> Result<Self, BtComponentClassInitializeMethodStatus> {
+                let trace_class = BtTraceClass::create(&self_component.as_self_component_inline());
+                let stream_class = BtStreamClass::create(&trace_class);
+                let other_stream_class = BtStreamClass::create(&trace_class);
+                let event_class = BtEventClass::create(&other_stream_class);
+                let trace = BtTrace::create(&trace_class);
+                let stream = BtStream::create(&stream_class, &trace);
+                // The stream classes are checked before the message iterator is used
+                let self_message_iterator = BtSelfMessageIterator::empty();
+                let builder = MessageBuilder::new(&self_message_iterator);
+                match builder.event(&event_class.as_const(), &stream.as_const(), None, None) {
+                    Err(error) if error.kind() == ErrorKind::EventClassStreamMismatch => Ok(Source),
+                    _ => Err(BtComponentClassInitializeMethodStatus::Error),
+                }
+            }
+        }
+
+        let iterator_class = message_iterator::create_class::<Iterator>().unwrap();
+        let source_class =
+            source::create_component_class::<Source>("source", &iterator_class).unwrap();
+        let params = BtValue::map_create();
+        let mut graph = BtGraph::create(0);
+        let result = graph.try_add_source_component(
+            &source_class.as_const(),
+            "source",
+            &params.as_const(),
+            BtLoggingLevel::None,
+        );
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn plugin_descriptor_layout() {
+        use crate::plugin::*;
+        use std::mem::size_of;
+        // Has to match the packed structures of babeltrace2/plugin/plugin-dev.h
+        assert_eq!(size_of::<PluginDescriptor>(), 16);
+        assert_eq!(size_of::<PluginAttribute>(), 44);
+        assert_eq!(size_of::<ComponentClassDescriptor>(), 28);
+        assert_eq!(size_of::<ComponentClassAttribute>(), 28);
+    }
 }
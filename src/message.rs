@@ -0,0 +1,559 @@
+// Copyright 2023 - 2023, Karsten Becker and the rust babeltrace2-plugin contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+//! Messages by type
+//!
+//! [`BtMessageConst::kind`] tells the type of a message along with accessors that are valid
+//! for it, so a match replaces calling the `BtMessageConst` functions of the right type:
+//!
+//! ```ignore
+//! for message in iterator_to_vec(&mut iterator)? {
+//!     match message.kind() {
+//!         MessageKind::Event(event) => handle(&event.event()),
+//!         MessageKind::DiscardedEvents(discarded) => lost += discarded.count().unwrap_or(0),
+//!         _ => {}
+//!     }
+//! }
+//! ```
+//!
+//! A [`MessageBuilder`] creates messages, checking the arguments against the stream class.
+use crate::bt2::*;
+use crate::error::{Error, ErrorKind, Result};
+
+/// A message, by type
+#[derive(Clone, Copy)]
+pub enum MessageKind<'a> {
+    StreamBeginning(StreamMessage<'a>),
+    StreamEnd(StreamMessage<'a>),
+    PacketBeginning(PacketMessage<'a>),
+    PacketEnd(PacketMessage<'a>),
+    Event(EventMessage<'a>),
+    DiscardedEvents(DiscardedMessage<'a>),
+    DiscardedPackets(DiscardedMessage<'a>),
+    MessageIteratorInactivity(InactivityMessage<'a>),
+}
+
+impl BtMessageConst {
+    /// The type of the message, with its accessors
+    pub fn kind(&self) -> MessageKind<'_> {
+        match self.get_type() {
+            BtMessageType::StreamBeginning => MessageKind::StreamBeginning(StreamMessage(self)),
+            BtMessageType::StreamEnd => MessageKind::StreamEnd(StreamMessage(self)),
+            BtMessageType::PacketBeginning => MessageKind::PacketBeginning(PacketMessage(self)),
+            BtMessageType::PacketEnd => MessageKind::PacketEnd(PacketMessage(self)),
+            BtMessageType::Event => MessageKind::Event(EventMessage(self)),
+            BtMessageType::DiscardedEvents => MessageKind::DiscardedEvents(DiscardedMessage(self)),
+            BtMessageType::DiscardedPackets => {
+                MessageKind::DiscardedPackets(DiscardedMessage(self))
+            }
+            BtMessageType::MessageIteratorInactivity => {
+                MessageKind::MessageIteratorInactivity(InactivityMessage(self))
+            }
+        }
+    }
+}
+
+impl<'a> MessageKind<'a> {
+    /// The message
+    pub fn message(&self) -> &'a BtMessageConst {
+        match self {
+            MessageKind::StreamBeginning(message) | MessageKind::StreamEnd(message) => message.0,
+            MessageKind::PacketBeginning(message) | MessageKind::PacketEnd(message) => message.0,
+            MessageKind::Event(message) => message.0,
+            MessageKind::DiscardedEvents(message) | MessageKind::DiscardedPackets(message) => {
+                message.0
+            }
+            MessageKind::MessageIteratorInactivity(message) => message.0,
+        }
+    }
+
+    /// The stream the message belongs to, none for inactivity messages
+    pub fn stream(&self) -> Option<BtStreamConstRef<'a>> {
+        match self {
+            MessageKind::StreamBeginning(message) | MessageKind::StreamEnd(message) => {
+                Some(message.stream())
+            }
+            MessageKind::PacketBeginning(message) | MessageKind::PacketEnd(message) => {
+                Some(message.stream())
+            }
+            MessageKind::Event(message) => Some(message.stream()),
+            MessageKind::DiscardedEvents(message) | MessageKind::DiscardedPackets(message) => {
+                Some(message.stream())
+            }
+            MessageKind::MessageIteratorInactivity(_) => None,
+        }
+    }
+
+    /// The default clock snapshot, if the message has one. Discarded messages have a
+    /// beginning and an end one, this is the beginning one.
+    pub fn default_clock_snapshot(&self) -> Option<BtClockSnapshotConstRef<'a>> {
+        match self {
+            MessageKind::StreamBeginning(message) | MessageKind::StreamEnd(message) => {
+                message.default_clock_snapshot()
+            }
+            MessageKind::PacketBeginning(message) | MessageKind::PacketEnd(message) => {
+                message.default_clock_snapshot()
+            }
+            MessageKind::Event(message) => message.default_clock_snapshot(),
+            MessageKind::DiscardedEvents(message) | MessageKind::DiscardedPackets(message) => {
+                message.beginning_default_clock_snapshot()
+            }
+            MessageKind::MessageIteratorInactivity(message) => Some(message.clock_snapshot()),
+        }
+    }
+}
+
+/// A stream beginning or stream end message
+#[derive(Clone, Copy)]
+pub struct StreamMessage<'a>(&'a BtMessageConst);
+
+impl<'a> StreamMessage<'a> {
+    pub fn stream(&self) -> BtStreamConstRef<'a> {
+        match self.0.get_type() {
+            BtMessageType::StreamBeginning => self.0.stream_beginning_borrow_stream_const(),
+            _ => self.0.stream_end_borrow_stream_const(),
+        }
+    }
+
+    /// None if the stream class has no default clock class or the snapshot is unknown
+    pub fn default_clock_snapshot(&self) -> Option<BtClockSnapshotConstRef<'a>> {
+        let clock_class = match self.0.get_type() {
+            BtMessageType::StreamBeginning => self
+                .0
+                .stream_beginning_borrow_stream_class_default_clock_class_const(),
+            _ => self
+                .0
+                .stream_end_borrow_stream_class_default_clock_class_const(),
+        };
+        if clock_class.as_ptr().is_null() {
+            return None;
+        }
+        match self.0.get_type() {
+            BtMessageType::StreamBeginning => self
+                .0
+                .stream_beginning_borrow_default_clock_snapshot_const(),
+            _ => self.0.stream_end_borrow_default_clock_snapshot_const(),
+        }
+    }
+}
+
+/// A packet beginning or packet end message
+#[derive(Clone, Copy)]
+pub struct PacketMessage<'a>(&'a BtMessageConst);
+
+impl<'a> PacketMessage<'a> {
+    fn is_beginning(&self) -> bool {
+        self.0.get_type() == BtMessageType::PacketBeginning
+    }
+
+    pub fn packet(&self) -> BtPacketConstRef<'a> {
+        if self.is_beginning() {
+            self.0.packet_beginning_borrow_packet_const()
+        } else {
+            self.0.packet_end_borrow_packet_const()
+        }
+    }
+
+    pub fn stream(&self) -> BtStreamConstRef<'a> {
+        self.packet().into_stream_const()
+    }
+
+    /// None if the stream class has no default clock snapshot for this kind of packet message
+    pub fn default_clock_snapshot(&self) -> Option<BtClockSnapshotConstRef<'a>> {
+        let stream = self.stream();
+        let stream_class = stream.borrow_class_const();
+        if self.is_beginning() {
+            stream_class
+                .packets_have_beginning_default_clock_snapshot()
+                .then(|| {
+                    self.0
+                        .packet_beginning_borrow_default_clock_snapshot_const()
+                })
+        } else {
+            stream_class
+                .packets_have_end_default_clock_snapshot()
+                .then(|| self.0.packet_end_borrow_default_clock_snapshot_const())
+        }
+    }
+}
+
+/// An event message
+#[derive(Clone, Copy)]
+pub struct EventMessage<'a>(&'a BtMessageConst);
+
+impl<'a> EventMessage<'a> {
+    pub fn event(&self) -> BtEventConstRef<'a> {
+        self.0.event_borrow_event_const()
+    }
+
+    pub fn stream(&self) -> BtStreamConstRef<'a> {
+        self.event().into_stream_const()
+    }
+
+    /// None if the stream has no packets
+    pub fn packet(&self) -> Option<BtPacketConstRef<'a>> {
+        self.event().into_packet_const()
+    }
+
+    /// None if the stream class has no default clock class
+    pub fn default_clock_snapshot(&self) -> Option<BtClockSnapshotConstRef<'a>> {
+        let clock_class = self.0.event_borrow_stream_class_default_clock_class_const();
+        (!clock_class.as_ptr().is_null())
+            .then(|| self.0.event_borrow_default_clock_snapshot_const())
+    }
+}
+
+/// A discarded events or discarded packets message
+#[derive(Clone, Copy)]
+pub struct DiscardedMessage<'a>(&'a BtMessageConst);
+
+impl<'a> DiscardedMessage<'a> {
+    fn is_events(&self) -> bool {
+        self.0.get_type() == BtMessageType::DiscardedEvents
+    }
+
+    pub fn stream(&self) -> BtStreamConstRef<'a> {
+        if self.is_events() {
+            self.0.discarded_events_borrow_stream_const()
+        } else {
+            self.0.discarded_packets_borrow_stream_const()
+        }
+    }
+
+    /// The number of discarded events or packets, if it is known
+    pub fn count(&self) -> Option<u64> {
+        if self.is_events() {
+            self.0.discarded_events_get_count()
+        } else {
+            self.0.discarded_packets_get_count()
+        }
+    }
+
+    fn has_default_clock_snapshots(&self) -> bool {
+        let stream = self.stream();
+        let stream_class = stream.borrow_class_const();
+        if self.is_events() {
+            stream_class.discarded_events_have_default_clock_snapshots()
+        } else {
+            stream_class.discarded_packets_have_default_clock_snapshots()
+        }
+    }
+
+    /// The time of the first discarded item, if the stream class has one
+    pub fn beginning_default_clock_snapshot(&self) -> Option<BtClockSnapshotConstRef<'a>> {
+        self.has_default_clock_snapshots().then(|| {
+            if self.is_events() {
+                self.0
+                    .discarded_events_borrow_beginning_default_clock_snapshot_const()
+            } else {
+                self.0
+                    .discarded_packets_borrow_beginning_default_clock_snapshot_const()
+            }
+        })
+    }
+
+    /// The time of the last discarded item, if the stream class has one
+    pub fn end_default_clock_snapshot(&self) -> Option<BtClockSnapshotConstRef<'a>> {
+        self.has_default_clock_snapshots().then(|| {
+            if self.is_events() {
+                self.0
+                    .discarded_events_borrow_end_default_clock_snapshot_const()
+            } else {
+                self.0
+                    .discarded_packets_borrow_end_default_clock_snapshot_const()
+            }
+        })
+    }
+}
+
+/// A message iterator inactivity message
+#[derive(Clone, Copy)]
+pub struct InactivityMessage<'a>(&'a BtMessageConst);
+
+impl<'a> InactivityMessage<'a> {
+    pub fn clock_snapshot(&self) -> BtClockSnapshotConstRef<'a> {
+        self.0
+            .message_iterator_inactivity_borrow_clock_snapshot_const()
+    }
+}
+
+/// Creates the messages of a message iterator. The constructor of libbabeltrace2 is chosen
+/// by whether the stream class has packets and default clock snapshots, and an error is
+/// returned if the arguments do not match it.
+///
+/// ```ignore
+/// let builder = MessageBuilder::new(self_message_iterator);
+/// messages.push(builder.stream_beginning(&stream, None)?);
+/// messages.push(builder.event(&event_class, &stream, None, Some(timestamp))?);
+/// ```
+#[derive(Clone, Copy)]
+pub struct MessageBuilder<'a> {
+    self_message_iterator: &'a BtSelfMessageIterator,
+}
+
+fn builder_error(kind: ErrorKind) -> Error {
+    Error::new(kind, "MessageBuilder")
+}
+
+/// Fails if the message could not be created
+fn created(message: BtMessageOwned) -> Result<BtMessageOwned> {
+    if message.as_ptr().is_null() {
+        Err(builder_error(ErrorKind::MemoryError))
+    } else {
+        Ok(message)
+    }
+}
+
+/// Checks the clock snapshots and the count of a discarded events or packets message
+fn check_discarded(clock_snapshots: Option<(u64, u64)>, count: Option<u64>) -> Result<()> {
+    if matches!(clock_snapshots, Some((beginning, end)) if beginning > end) {
+        return Err(builder_error(ErrorKind::ClockSnapshotOutOfOrder));
+    }
+    if count == Some(0) {
+        return Err(builder_error(ErrorKind::UserError));
+    }
+    Ok(())
+}
+
+/// Checks that a clock snapshot is given exactly if the stream class has one
+fn check_clock_snapshot<T>(has_clock_snapshot: bool, clock_snapshot: Option<T>) -> Result<()> {
+    match (has_clock_snapshot, clock_snapshot.is_some()) {
+        (true, false) => Err(builder_error(ErrorKind::ClockSnapshotRequired)),
+        (false, true) => Err(builder_error(ErrorKind::ClockSnapshotNotSupported)),
+        _ => Ok(()),
+    }
+}
+
+impl<'a> MessageBuilder<'a> {
+    pub fn new(self_message_iterator: &'a BtSelfMessageIterator) -> MessageBuilder<'a> {
+        MessageBuilder {
+            self_message_iterator,
+        }
+    }
+
+    fn has_default_clock_class(stream: &BtStreamConst) -> bool {
+        !stream
+            .borrow_class_const()
+            .borrow_default_clock_class_const()
+            .as_ptr()
+            .is_null()
+    }
+
+    /// A stream beginning message. The default clock snapshot is optional if the stream class
+    /// has a default clock class, as it may be unknown.
+    pub fn stream_beginning(
+        &self,
+        stream: &BtStreamConst,
+        clock_snapshot: Option<u64>,
+    ) -> Result<BtMessageOwned> {
+        if clock_snapshot.is_some() && !Self::has_default_clock_class(stream) {
+            return Err(builder_error(ErrorKind::ClockSnapshotNotSupported));
+        }
+        let mut message = created(BtMessage::stream_beginning_create(
+            self.self_message_iterator,
+            stream,
+        ))?;
+        if let Some(value) = clock_snapshot {
+            message.stream_beginning_set_default_clock_snapshot(value);
+        }
+        Ok(message)
+    }
+
+    /// A stream end message. The default clock snapshot is optional if the stream class has a
+    /// default clock class, as it may be unknown.
+    pub fn stream_end(
+        &self,
+        stream: &BtStreamConst,
+        clock_snapshot: Option<u64>,
+    ) -> Result<BtMessageOwned> {
+        if clock_snapshot.is_some() && !Self::has_default_clock_class(stream) {
+            return Err(builder_error(ErrorKind::ClockSnapshotNotSupported));
+        }
+        let mut message = created(BtMessage::stream_end_create(
+            self.self_message_iterator,
+            stream,
+        ))?;
+        if let Some(value) = clock_snapshot {
+            message.stream_end_set_default_clock_snapshot(value);
+        }
+        Ok(message)
+    }
+
+    /// A packet beginning message, the default clock snapshot is needed if the stream class
+    /// has packet beginning default clock snapshots.
+    pub fn packet_beginning(
+        &self,
+        packet: &BtPacketConst,
+        clock_snapshot: Option<u64>,
+    ) -> Result<BtMessageOwned> {
+        let stream = packet.borrow_stream_const();
+        let stream_class = stream.borrow_class_const();
+        check_clock_snapshot(
+            stream_class.packets_have_beginning_default_clock_snapshot(),
+            clock_snapshot,
+        )?;
+        created(match clock_snapshot {
+            Some(value) => BtMessage::packet_beginning_create_with_default_clock_snapshot(
+                self.self_message_iterator,
+                packet,
+                value,
+            ),
+            None => BtMessage::packet_beginning_create(self.self_message_iterator, packet),
+        })
+    }
+
+    /// A packet end message, the default clock snapshot is needed if the stream class has
+    /// packet end default clock snapshots.
+    pub fn packet_end(
+        &self,
+        packet: &BtPacketConst,
+        clock_snapshot: Option<u64>,
+    ) -> Result<BtMessageOwned> {
+        let stream = packet.borrow_stream_const();
+        let stream_class = stream.borrow_class_const();
+        check_clock_snapshot(
+            stream_class.packets_have_end_default_clock_snapshot(),
+            clock_snapshot,
+        )?;
+        created(match clock_snapshot {
+            Some(value) => BtMessage::packet_end_create_with_default_clock_snapshot(
+                self.self_message_iterator,
+                packet,
+                value,
+            ),
+            None => BtMessage::packet_end_create(self.self_message_iterator, packet),
+        })
+    }
+
+    /// An event message in `stream`. The event class has to be one of the class of `stream`.
+    /// The packet is needed if the stream class has packets, and has to be one of `stream`, the
+    /// default clock snapshot is needed if the stream class has a default clock class.
+    pub fn event(
+        &self,
+        event_class: &BtEventClassConst,
+        stream: &BtStreamConst,
+        packet: Option<&BtPacketConst>,
+        clock_snapshot: Option<u64>,
+    ) -> Result<BtMessageOwned> {
+        let stream_class = stream.borrow_class_const();
+        if event_class.borrow_stream_class_const().as_ptr() != stream_class.as_ptr() {
+            return Err(builder_error(ErrorKind::EventClassStreamMismatch));
+        }
+        match (stream_class.supports_packets(), packet.is_some()) {
+            (true, false) => return Err(builder_error(ErrorKind::PacketRequired)),
+            (false, true) => return Err(builder_error(ErrorKind::PacketNotSupported)),
+            _ => {}
+        }
+        if let Some(packet) = packet {
+            if packet.borrow_stream_const().as_ptr() != stream.as_ptr() {
+                return Err(builder_error(ErrorKind::PacketStreamMismatch));
+            }
+        }
+        check_clock_snapshot(Self::has_default_clock_class(stream), clock_snapshot)?;
+        let iterator = self.self_message_iterator;
+        created(match (packet, clock_snapshot) {
+            (Some(packet), Some(value)) => {
+                BtMessage::event_create_with_packet_and_default_clock_snapshot(
+                    iterator,
+                    event_class,
+                    packet,
+                    value,
+                )
+            }
+            (Some(packet), None) => {
+                BtMessage::event_create_with_packet(iterator, event_class, packet)
+            }
+            (None, Some(value)) => BtMessage::event_create_with_default_clock_snapshot(
+                iterator,
+                event_class,
+                stream,
+                value,
+            ),
+            (None, None) => BtMessage::event_create(iterator, event_class, stream),
+        })
+    }
+
+    /// A discarded events message. The beginning and end default clock snapshots are needed if
+    /// the stream class has them for discarded events, `count` is the number of discarded
+    /// events if it is known. The beginning can't be after the end, and the
+    /// count can't be 0.
+    pub fn discarded_events(
+        &self,
+        stream: &BtStreamConst,
+        clock_snapshots: Option<(u64, u64)>,
+        count: Option<u64>,
+    ) -> Result<BtMessageOwned> {
+        let stream_class = stream.borrow_class_const();
+        if !stream_class.supports_discarded_events() {
+            return Err(builder_error(ErrorKind::DiscardedNotSupported));
+        }
+        check_clock_snapshot(
+            stream_class.discarded_events_have_default_clock_snapshots(),
+            clock_snapshots,
+        )?;
+        check_discarded(clock_snapshots, count)?;
+        let mut message = created(match clock_snapshots {
+            Some((beginning, end)) => {
+                BtMessage::discarded_events_create_with_default_clock_snapshots(
+                    self.self_message_iterator,
+                    stream,
+                    beginning,
+                    end,
+                )
+            }
+            None => BtMessage::discarded_events_create(self.self_message_iterator, stream),
+        })?;
+        if let Some(count) = count {
+            message.discarded_events_set_count(count);
+        }
+        Ok(message)
+    }
+
+    /// A discarded packets message. The beginning and end default clock snapshots are needed
+    /// if the stream class has them for discarded packets, `count` is the number of discarded
+    /// packets if it is known. The beginning can't be after the end, and the
+    /// count can't be 0.
+    pub fn discarded_packets(
+        &self,
+        stream: &BtStreamConst,
+        clock_snapshots: Option<(u64, u64)>,
+        count: Option<u64>,
+    ) -> Result<BtMessageOwned> {
+        let stream_class = stream.borrow_class_const();
+        if !stream_class.supports_discarded_packets() {
+            return Err(builder_error(ErrorKind::DiscardedNotSupported));
+        }
+        check_clock_snapshot(
+            stream_class.discarded_packets_have_default_clock_snapshots(),
+            clock_snapshots,
+        )?;
+        check_discarded(clock_snapshots, count)?;
+        let mut message = created(match clock_snapshots {
+            Some((beginning, end)) => {
+                BtMessage::discarded_packets_create_with_default_clock_snapshots(
+                    self.self_message_iterator,
+                    stream,
+                    beginning,
+                    end,
+                )
+            }
+            None => BtMessage::discarded_packets_create(self.self_message_iterator, stream),
+        })?;
+        if let Some(count) = count {
+            message.discarded_packets_set_count(count);
+        }
+        Ok(message)
+    }
+
+    /// A message iterator inactivity message at `clock_snapshot` of `clock_class`
+    pub fn inactivity(
+        &self,
+        clock_class: &BtClockClassConst,
+        clock_snapshot: u64,
+    ) -> Result<BtMessageOwned> {
+        created(BtMessage::message_iterator_inactivity_create(
+            self.self_message_iterator,
+            clock_class,
+            clock_snapshot,
+        ))
+    }
+}
@@ -0,0 +1,301 @@
+// Copyright 2023 - 2023, Karsten Becker and the rust babeltrace2-plugin contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+//! Trait based message iterator classes
+//!
+//! Implement [`MessageIterator`] and either create the message iterator class at runtime
+//! with [`create_class`], for example to pass it to
+//! [`source::create_component_class`](crate::source::create_component_class), or reference
+//! the trampolines of this module (for example `message_iterator::next::<MyIterator>`) in
+//! [`bt_plugin!`](crate::bt_plugin). The trampolines are only meant to be called by
+//! libbabeltrace2. A panic of a method is caught by the trampolines, which return the error
+//! status instead.
+#![allow(clippy::missing_safety_doc)]
+use crate::bt2::binding::*;
+use crate::bt2::*;
+use crate::component::catch_panic;
+use crate::error::{Error, ErrorKind};
+
+/// The methods of a message iterator class. The implementing type is the private data of
+/// each message iterator, it is created by [`MessageIterator::initialize`] and dropped after
+/// [`MessageIterator::finalize`].
+pub trait MessageIterator: Sized {
+    /// Called when a message iterator is created on the output port `port`.
+    fn initialize(
+        self_message_iterator: &mut BtSelfMessageIterator,
+        configuration: &mut BtSelfMessageIteratorConfiguration,
+        port: &mut BtSelfComponentPortOutput,
+    ) -> Result<Self, BtMessageIteratorClassInitializeMethodStatus>;
+
+    /// Called before the message iterator is destroyed.
+    fn finalize(&mut self, _self_message_iterator: &mut BtSelfMessageIterator) {}
+
+    /// Called to get the next messages, which are pushed into `messages`.
+    ///
+    /// When messages were pushed, `End` and `Again` are turned into `Ok`. `Again` is then
+    /// reported by calling the method again, while `End` is reported on the next call without
+    /// calling the method, until a seek succeeds. When no message was pushed, `Ok` is turned
+    /// into `Again`. On errors the pushed messages are released.
+    fn next(
+        &mut self,
+        self_message_iterator: &mut BtSelfMessageIterator,
+        messages: &mut MessageBuffer,
+    ) -> BtMessageIteratorClassNextMethodStatus;
+
+    /// Called to check if the message iterator can seek its beginning.
+    fn can_seek_beginning(
+        &mut self,
+        _self_message_iterator: &mut BtSelfMessageIterator,
+    ) -> Result<bool, BtMessageIteratorClassCanSeekBeginningMethodStatus> {
+        Ok(false)
+    }
+
+    /// Called to seek the beginning, only if [`MessageIterator::can_seek_beginning`] returned true.
+    fn seek_beginning(
+        &mut self,
+        _self_message_iterator: &mut BtSelfMessageIterator,
+    ) -> BtMessageIteratorClassSeekBeginningMethodStatus {
+        BtMessageIteratorClassSeekBeginningMethodStatus::Error
+    }
+
+    /// Called to check if the message iterator can seek the time `ns_from_origin`.
+    fn can_seek_ns_from_origin(
+        &mut self,
+        _self_message_iterator: &mut BtSelfMessageIterator,
+        _ns_from_origin: i64,
+    ) -> Result<bool, BtMessageIteratorClassCanSeekNsFromOriginMethodStatus> {
+        Ok(false)
+    }
+
+    /// Called to seek the time `ns_from_origin`, only if
+    /// [`MessageIterator::can_seek_ns_from_origin`] returned true.
+    fn seek_ns_from_origin(
+        &mut self,
+        _self_message_iterator: &mut BtSelfMessageIterator,
+        _ns_from_origin: i64,
+    ) -> BtMessageIteratorClassSeekNsFromOriginMethodStatus {
+        BtMessageIteratorClassSeekNsFromOriginMethodStatus::Error
+    }
+}
+
+/// The output array of the next method, bounded by the capacity libbabeltrace2 provides
+pub struct MessageBuffer<'a> {
+    messages: &'a mut [*const bt_message],
+    count: usize,
+}
+
+impl<'a> MessageBuffer<'a> {
+    /// The maximum number of messages that can be pushed
+    pub fn capacity(&self) -> usize {
+        self.messages.len()
+    }
+
+    /// The number of messages pushed so far
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.count == self.messages.len()
+    }
+
+    /// Append `message` and pass its reference to the buffer. If the buffer is full, the
+    /// message is given back.
+    pub fn push(&mut self, message: BtMessageConstOwned) -> Result<(), BtMessageConstOwned> {
+        if self.is_full() {
+            return Err(message);
+        }
+        self.messages[self.count] = message.into_inner().as_ptr();
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Release the references of all pushed messages
+    fn clear(&mut self) {
+        for message in &self.messages[..self.count] {
+            drop(BtMessageConstOwned::from_ptr(*message));
+        }
+        self.count = 0;
+    }
+}
+
+/// Creates a message iterator class with the methods of `T`.
+pub fn create_class<T: MessageIterator>() -> crate::error::Result<BtMessageIteratorClassOwned> {
+    let mut message_iterator_class = BtMessageIteratorClass::create(Some(next::<T>));
+    if message_iterator_class.is_empty() {
+        return Err(Error::new(ErrorKind::MemoryError, "BtMessageIteratorClass"));
+    }
+    message_iterator_class.set_initialize_method(Some(initialize::<T>));
+    message_iterator_class.set_finalize_method(Some(finalize::<T>));
+    message_iterator_class.set_seek_beginning_methods(Some(seek_beginning::<T>), Some(can_seek_beginning::<T>));
+    message_iterator_class.set_seek_ns_from_origin_methods(
+        Some(seek_ns_from_origin::<T>),
+        Some(can_seek_ns_from_origin::<T>),
+    );
+    Ok(message_iterator_class)
+}
+
+/// The private data of a message iterator
+struct IteratorData<T> {
+    iterator: T,
+    /// `next` returned `End` together with messages, so the next call returns `End`
+    ended: bool,
+}
+
+unsafe fn borrow_iterator_data<'a, T>(
+    self_message_iterator: &BtSelfMessageIterator,
+) -> &'a mut IteratorData<T> {
+    &mut *(self_message_iterator.get_data() as *mut IteratorData<T>)
+}
+
+/// Trampoline for [`MessageIterator::initialize`]
+pub unsafe extern "C" fn initialize<T: MessageIterator>(
+    self_message_iterator: *mut bt_self_message_iterator,
+    configuration: *mut bt_self_message_iterator_configuration,
+    port: *mut bt_self_component_port_output,
+) -> bt_message_iterator_class_initialize_method_status {
+    let mut self_message_iterator = BtSelfMessageIterator::from_ptr(self_message_iterator);
+    let mut configuration = BtSelfMessageIteratorConfiguration::from_ptr(configuration);
+    let mut port = BtSelfComponentPortOutput::from_ptr(port);
+    let result = catch_panic(Err(BtMessageIteratorClassInitializeMethodStatus::Error), || {
+        T::initialize(&mut self_message_iterator, &mut configuration, &mut port)
+    });
+    match result {
+        Ok(iterator) => {
+            let data = IteratorData {
+                iterator,
+                ended: false,
+            };
+            self_message_iterator.set_data(Box::into_raw(Box::new(data)) as *mut std::ffi::c_void);
+            BtMessageIteratorClassInitializeMethodStatus::Ok.into()
+        }
+        Err(status) => status.into(),
+    }
+}
+
+/// Trampoline for [`MessageIterator::finalize`]
+pub unsafe extern "C" fn finalize<T: MessageIterator>(self_message_iterator: *mut bt_self_message_iterator) {
+    let mut self_message_iterator = BtSelfMessageIterator::from_ptr(self_message_iterator);
+    let data = self_message_iterator.get_data() as *mut IteratorData<T>;
+    if data.is_null() {
+        return;
+    }
+    let mut data = Box::from_raw(data);
+    catch_panic((), || data.iterator.finalize(&mut self_message_iterator));
+}
+
+/// Trampoline for [`MessageIterator::next`]
+pub unsafe extern "C" fn next<T: MessageIterator>(
+    self_message_iterator: *mut bt_self_message_iterator,
+    messages: bt_message_array_const,
+    capacity: u64,
+    count: *mut u64,
+) -> bt_message_iterator_class_next_method_status {
+    let mut self_message_iterator = BtSelfMessageIterator::from_ptr(self_message_iterator);
+    let data: &mut IteratorData<T> = borrow_iterator_data(&self_message_iterator);
+    if data.ended {
+        *count = 0;
+        return BtMessageIteratorClassNextMethodStatus::End.into();
+    }
+    let mut buffer = MessageBuffer {
+        messages: std::slice::from_raw_parts_mut(messages, capacity as usize),
+        count: 0,
+    };
+    let status = catch_panic(BtMessageIteratorClassNextMethodStatus::Error, || {
+        data.iterator.next(&mut self_message_iterator, &mut buffer)
+    });
+    let status = match status {
+        BtMessageIteratorClassNextMethodStatus::Ok if buffer.is_empty() => {
+            BtMessageIteratorClassNextMethodStatus::Again
+        }
+        BtMessageIteratorClassNextMethodStatus::End if !buffer.is_empty() => {
+            data.ended = true;
+            BtMessageIteratorClassNextMethodStatus::Ok
+        }
+        BtMessageIteratorClassNextMethodStatus::Again if !buffer.is_empty() => {
+            BtMessageIteratorClassNextMethodStatus::Ok
+        }
+        BtMessageIteratorClassNextMethodStatus::Ok
+        | BtMessageIteratorClassNextMethodStatus::End
+        | BtMessageIteratorClassNextMethodStatus::Again => status,
+        BtMessageIteratorClassNextMethodStatus::MemoryError | BtMessageIteratorClassNextMethodStatus::Error => {
+            buffer.clear();
+            status
+        }
+    };
+    *count = buffer.len() as u64;
+    status.into()
+}
+
+/// Trampoline for [`MessageIterator::can_seek_beginning`]
+pub unsafe extern "C" fn can_seek_beginning<T: MessageIterator>(
+    self_message_iterator: *mut bt_self_message_iterator,
+    can_seek_beginning: *mut bt_bool,
+) -> bt_message_iterator_class_can_seek_beginning_method_status {
+    let mut self_message_iterator = BtSelfMessageIterator::from_ptr(self_message_iterator);
+    let data: &mut IteratorData<T> = borrow_iterator_data(&self_message_iterator);
+    let result = catch_panic(Err(BtMessageIteratorClassCanSeekBeginningMethodStatus::Error), || {
+        data.iterator.can_seek_beginning(&mut self_message_iterator)
+    });
+    match result {
+        Ok(can_seek) => {
+            *can_seek_beginning = if can_seek { BT_TRUE } else { BT_FALSE } as bt_bool;
+            BtMessageIteratorClassCanSeekBeginningMethodStatus::Ok.into()
+        }
+        Err(status) => status.into(),
+    }
+}
+
+/// Trampoline for [`MessageIterator::seek_beginning`]
+pub unsafe extern "C" fn seek_beginning<T: MessageIterator>(
+    self_message_iterator: *mut bt_self_message_iterator,
+) -> bt_message_iterator_class_seek_beginning_method_status {
+    let mut self_message_iterator = BtSelfMessageIterator::from_ptr(self_message_iterator);
+    let data: &mut IteratorData<T> = borrow_iterator_data(&self_message_iterator);
+    let status = catch_panic(BtMessageIteratorClassSeekBeginningMethodStatus::Error, || {
+        data.iterator.seek_beginning(&mut self_message_iterator)
+    });
+    if status == BtMessageIteratorClassSeekBeginningMethodStatus::Ok {
+        data.ended = false;
+    }
+    status.into()
+}
+
+/// Trampoline for [`MessageIterator::can_seek_ns_from_origin`]
+pub unsafe extern "C" fn can_seek_ns_from_origin<T: MessageIterator>(
+    self_message_iterator: *mut bt_self_message_iterator,
+    ns_from_origin: i64,
+    can_seek_ns_from_origin: *mut bt_bool,
+) -> bt_message_iterator_class_can_seek_ns_from_origin_method_status {
+    let mut self_message_iterator = BtSelfMessageIterator::from_ptr(self_message_iterator);
+    let data: &mut IteratorData<T> = borrow_iterator_data(&self_message_iterator);
+    let result = catch_panic(Err(BtMessageIteratorClassCanSeekNsFromOriginMethodStatus::Error), || {
+        data.iterator.can_seek_ns_from_origin(&mut self_message_iterator, ns_from_origin)
+    });
+    match result {
+        Ok(can_seek) => {
+            *can_seek_ns_from_origin = if can_seek { BT_TRUE } else { BT_FALSE } as bt_bool;
+            BtMessageIteratorClassCanSeekNsFromOriginMethodStatus::Ok.into()
+        }
+        Err(status) => status.into(),
+    }
+}
+
+/// Trampoline for [`MessageIterator::seek_ns_from_origin`]
+pub unsafe extern "C" fn seek_ns_from_origin<T: MessageIterator>(
+    self_message_iterator: *mut bt_self_message_iterator,
+    ns_from_origin: i64,
+) -> bt_message_iterator_class_seek_ns_from_origin_method_status {
+    let mut self_message_iterator = BtSelfMessageIterator::from_ptr(self_message_iterator);
+    let data: &mut IteratorData<T> = borrow_iterator_data(&self_message_iterator);
+    let status = catch_panic(BtMessageIteratorClassSeekNsFromOriginMethodStatus::Error, || {
+        data.iterator.seek_ns_from_origin(&mut self_message_iterator, ns_from_origin)
+    });
+    if status == BtMessageIteratorClassSeekNsFromOriginMethodStatus::Ok {
+        data.ended = false;
+    }
+    status.into()
+}
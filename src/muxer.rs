@@ -0,0 +1,194 @@
+// Copyright 2023 - 2023, Karsten Becker and the rust babeltrace2-plugin contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+//! Merging the messages of several upstream message iterators in time order
+//!
+//! A [`Muxer`] keeps the next message of each input and returns the one with the earliest
+//! default clock snapshot, like `filter.utils.muxer`. A message without a clock snapshot
+//! gets the time of the previous message of its input, so it stays in place within its
+//! input. Messages of the same time are returned in the order they were received.
+//!
+//! ```ignore
+//! fn initialize(self_message_iterator: &mut BtSelfMessageIterator, ..) -> Result<Self, _> {
+//!     Ok(MyFilterIterator { muxer: UpstreamIterators::create(self_message_iterator)?.into() })
+//! }
+//!
+//! fn next(&mut self, self_message_iterator: &mut BtSelfMessageIterator, messages: &mut MessageBuffer) -> BtMessageIteratorClassNextMethodStatus {
+//!     self.muxer.next_into(self_message_iterator, messages)
+//! }
+//! ```
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+use crate::bt2::*;
+use crate::current_thread::ErrorActor;
+use crate::error::{Error, ErrorKind, Result};
+use crate::message_iterator::MessageBuffer;
+use crate::upstream::{MessagePoll, UpstreamIterator, UpstreamIterators};
+
+struct Head<T> {
+    ns_from_origin: i64,
+    sequence: u64,
+    input: usize,
+    item: T,
+}
+
+impl<T> Head<T> {
+    fn key(&self) -> (i64, u64) {
+        (self.ns_from_origin, self.sequence)
+    }
+}
+
+impl<T> PartialEq for Head<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl<T> Eq for Head<T> {}
+
+impl<T> PartialOrd for Head<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Head<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+/// The next items of the inputs, the earliest is popped first and items of the same time in
+/// the order they were pushed
+pub(crate) struct Heads<T> {
+    heap: BinaryHeap<Reverse<Head<T>>>,
+    sequence: u64,
+}
+
+impl<T> Heads<T> {
+    pub(crate) fn new() -> Heads<T> {
+        Heads {
+            heap: BinaryHeap::new(),
+            sequence: 0,
+        }
+    }
+
+    pub(crate) fn push(&mut self, input: usize, ns_from_origin: i64, item: T) {
+        self.heap.push(Reverse(Head {
+            ns_from_origin,
+            sequence: self.sequence,
+            input,
+            item,
+        }));
+        self.sequence += 1;
+    }
+
+    /// The earliest item and its input
+    pub(crate) fn pop(&mut self) -> Option<(usize, T)> {
+        self.heap.pop().map(|Reverse(head)| (head.input, head.item))
+    }
+}
+
+/// Merges the messages of upstream message iterators by their default clock snapshots
+pub struct Muxer {
+    inputs: Vec<UpstreamIterator>,
+    /// The time of the last message of each input
+    last_ns_from_origin: Vec<i64>,
+    /// The inputs which have no message in `heads` and did not end
+    needing_head: Vec<usize>,
+    heads: Heads<BtMessageConstOwned>,
+    /// The error of a message whose time could not be determined, the messages can't be
+    /// merged afterwards
+    error: Option<Error>,
+}
+
+impl Muxer {
+    pub fn new(inputs: Vec<UpstreamIterator>) -> Muxer {
+        Muxer {
+            last_ns_from_origin: vec![i64::MIN; inputs.len()],
+            needing_head: (0..inputs.len()).collect(),
+            heads: Heads::new(),
+            error: None,
+            inputs,
+        }
+    }
+
+    /// The time of `message`, or the time of the previous message of `input` if it has no
+    /// clock snapshot
+    fn ns_from_origin(&mut self, input: usize, message: &BtMessageConst) -> Result<i64> {
+        if let Some(clock_snapshot) = message.kind().default_clock_snapshot() {
+            let ns_from_origin = clock_snapshot
+                .get_ns_from_origin()
+                .map_err(|status| status.into_result().unwrap_err())?;
+            self.last_ns_from_origin[input] = ns_from_origin;
+        }
+        Ok(self.last_ns_from_origin[input])
+    }
+
+    /// The next message of all inputs. `Again` is returned if an input without a queued
+    /// message returns it, the other inputs keep their messages. Once the time of a message
+    /// can't be determined, its error is returned by every call.
+    pub fn poll_next(&mut self) -> Result<MessagePoll<BtMessageConstOwned>> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        while let Some(&input) = self.needing_head.last() {
+            match self.inputs[input].poll_next()? {
+                MessagePoll::Ready(message) => match self.ns_from_origin(input, &message) {
+                    Ok(ns_from_origin) => self.heads.push(input, ns_from_origin, message),
+                    Err(error) => {
+                        self.error = Some(error);
+                        return Err(error);
+                    }
+                },
+                MessagePoll::Again => return Ok(MessagePoll::Again),
+                MessagePoll::End => {}
+            }
+            self.needing_head.pop();
+        }
+        Ok(match self.heads.pop() {
+            Some((input, message)) => {
+                self.needing_head.push(input);
+                MessagePoll::Ready(message)
+            }
+            None => MessagePoll::End,
+        })
+    }
+
+    /// Push messages to `messages` until it is full or no message is available. The returned
+    /// status is the one of the `next` method of the message iterator, an error cause
+    /// attributed to `actor` is appended before returning an error.
+    pub fn next_into(
+        &mut self,
+        actor: &mut impl ErrorActor,
+        messages: &mut MessageBuffer,
+    ) -> BtMessageIteratorClassNextMethodStatus {
+        while !messages.is_full() {
+            match self.poll_next() {
+                // Cannot fail, the buffer is not full
+                Ok(MessagePoll::Ready(message)) => drop(messages.push(message)),
+                Ok(MessagePoll::Again) => return BtMessageIteratorClassNextMethodStatus::Again,
+                Ok(MessagePoll::End) => return BtMessageIteratorClassNextMethodStatus::End,
+                Err(error) => {
+                    actor.append_error_cause(
+                        file!(),
+                        u64::from(line!()),
+                        &format!("Cannot merge the upstream messages: {error}"),
+                    );
+                    return if error.kind() == ErrorKind::MemoryError {
+                        BtMessageIteratorClassNextMethodStatus::MemoryError
+                    } else {
+                        BtMessageIteratorClassNextMethodStatus::Error
+                    };
+                }
+            }
+        }
+        BtMessageIteratorClassNextMethodStatus::Ok
+    }
+}
+
+impl From<UpstreamIterators> for Muxer {
+    fn from(iterators: UpstreamIterators) -> Muxer {
+        Muxer::new(iterators.into_iterators())
+    }
+}
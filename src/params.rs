@@ -0,0 +1,301 @@
+// Copyright 2023 - 2023, Karsten Becker and the rust babeltrace2-plugin contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+//! Declared initialization parameters of component classes
+//!
+//! Set the `PARAMS` constant of a component trait to a [`ParamSchema`]. The initialize
+//! trampoline then rejects unknown parameters, missing required ones and ones of the wrong
+//! type with an error cause each, and passes the parameters with the defaults filled in to
+//! `initialize`. `create_component_class` also sets the description and the help text of the
+//! component class from the schema. The component classes of
+//! [`bt_plugin!`](crate::bt_plugin) only get their parameters validated, the schema is not
+//! used for their description and help text.
+//!
+//! ```ignore
+//! const PARAMS: Option<ParamSchema> = Some(ParamSchema::new(
+//!     "Read samples from a file",
+//!     &[
+//!         Param::required("path", BtValueType::String, "The file to read"),
+//!         Param::optional("verbose", BtValueType::Bool, "Log every sample")
+//!             .default(ParamDefault::Bool(false)),
+//!     ],
+//! ));
+//! ```
+use std::fmt::Write;
+
+use crate::bt2::*;
+use crate::error::ErrorKind;
+
+/// The default value of an optional parameter
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParamDefault {
+    Bool(bool),
+    UnsignedInteger(u64),
+    SignedInteger(i64),
+    Real(f64),
+    String(&'static str),
+}
+
+impl ParamDefault {
+    /// A new value holding the default, fails if the value could not be created or if a
+    /// string contains a NUL character
+    pub fn to_value(&self) -> crate::error::Result<BtValueOwned> {
+        match *self {
+            ParamDefault::Bool(value) => value.try_into(),
+            ParamDefault::UnsignedInteger(value) => value.try_into(),
+            ParamDefault::SignedInteger(value) => value.try_into(),
+            ParamDefault::Real(value) => value.try_into(),
+            ParamDefault::String(value) => value.try_into(),
+        }
+    }
+}
+
+impl std::fmt::Display for ParamDefault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParamDefault::Bool(value) => write!(f, "{value}"),
+            ParamDefault::UnsignedInteger(value) => write!(f, "{value}"),
+            ParamDefault::SignedInteger(value) => write!(f, "{value}"),
+            ParamDefault::Real(value) => write!(f, "{value}"),
+            ParamDefault::String(value) => write!(f, "{value:?}"),
+        }
+    }
+}
+
+/// An initialization parameter. `value_type` is the type of the value, where
+/// `BtValueType::Integer` accepts both signed and unsigned integers, `UnsignedInteger` also
+/// accepts non-negative signed integers and `Real` also accepts integers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Param {
+    pub name: &'static str,
+    pub value_type: BtValueType,
+    pub required: bool,
+    pub default: Option<ParamDefault>,
+    pub description: &'static str,
+}
+
+impl Param {
+    pub const fn required(
+        name: &'static str,
+        value_type: BtValueType,
+        description: &'static str,
+    ) -> Param {
+        Param {
+            name,
+            value_type,
+            required: true,
+            default: None,
+            description,
+        }
+    }
+
+    pub const fn optional(
+        name: &'static str,
+        value_type: BtValueType,
+        description: &'static str,
+    ) -> Param {
+        Param {
+            name,
+            value_type,
+            required: false,
+            default: None,
+            description,
+        }
+    }
+
+    /// The value used when the parameter is not given. Panics, at compile time in constants, if
+    /// `value_type` does not accept the default.
+    pub const fn default(self, default: ParamDefault) -> Param {
+        assert!(
+            accepts_default(self.value_type, default),
+            "The default does not have the type of the parameter"
+        );
+        Param {
+            default: Some(default),
+            ..self
+        }
+    }
+
+    fn accepts(&self, value: &BtValueConst) -> bool {
+        match self.value_type {
+            BtValueType::Integer => matches!(
+                value.get_type(),
+                BtValueType::UnsignedInteger | BtValueType::SignedInteger
+            ),
+            BtValueType::UnsignedInteger => u64::try_from(value).is_ok(),
+            BtValueType::Real => f64::try_from(value).is_ok(),
+            value_type => value.get_type() == value_type,
+        }
+    }
+}
+
+/// The same as [`Param::accepts`] for a default
+const fn accepts_default(value_type: BtValueType, default: ParamDefault) -> bool {
+    match default {
+        ParamDefault::Bool(_) => matches!(value_type, BtValueType::Bool),
+        ParamDefault::UnsignedInteger(_) => matches!(
+            value_type,
+            BtValueType::UnsignedInteger | BtValueType::Integer | BtValueType::Real
+        ),
+        ParamDefault::SignedInteger(value) => match value_type {
+            BtValueType::SignedInteger | BtValueType::Integer | BtValueType::Real => true,
+            BtValueType::UnsignedInteger => value >= 0,
+            _ => false,
+        },
+        ParamDefault::Real(_) => matches!(value_type, BtValueType::Real),
+        ParamDefault::String(_) => matches!(value_type, BtValueType::String),
+    }
+}
+
+fn type_name(value_type: BtValueType) -> &'static str {
+    match value_type {
+        BtValueType::Null => "null",
+        BtValueType::Bool => "boolean",
+        BtValueType::Integer => "integer",
+        BtValueType::UnsignedInteger => "unsigned integer",
+        BtValueType::SignedInteger => "signed integer",
+        BtValueType::Real => "real",
+        BtValueType::String => "string",
+        BtValueType::Array => "array",
+        BtValueType::Map => "map",
+    }
+}
+
+/// The initialization parameters of a component class
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParamSchema {
+    /// The description set by `create_component_class`
+    pub description: &'static str,
+    pub params: &'static [Param],
+}
+
+impl ParamSchema {
+    pub const fn new(description: &'static str, params: &'static [Param]) -> ParamSchema {
+        ParamSchema {
+            description,
+            params,
+        }
+    }
+
+    /// The help text set by `create_component_class`, listing the parameters
+    pub fn help(&self) -> String {
+        let mut help = String::from("Parameters:\n");
+        for param in self.params {
+            let _ = write!(help, "\n  {} ({}", param.name, type_name(param.value_type));
+            if param.required {
+                help.push_str(", required");
+            }
+            if let Some(default) = param.default {
+                let _ = write!(help, ", default: {default}");
+            }
+            let _ = writeln!(help, ")\n    {}", param.description);
+        }
+        help
+    }
+
+    /// A message for each problem of `params`
+    pub fn validate(&self, params: &BtValueConst) -> Vec<String> {
+        if params.get_type() != BtValueType::Map {
+            return vec![format!(
+                "The parameters are a {}, not a map",
+                type_name(params.get_type())
+            )];
+        }
+        let mut problems = Vec::new();
+        for (key, value) in params.map_entries() {
+            let key = key.to_string_lossy();
+            match self.params.iter().find(|param| param.name == key) {
+                None => problems.push(format!("Unknown parameter `{key}`")),
+                Some(param) if !param.accepts(&value) => problems.push(format!(
+                    "Parameter `{key}` is a {}, expected a {}",
+                    type_name(value.get_type()),
+                    type_name(param.value_type)
+                )),
+                Some(_) => {}
+            }
+        }
+        for param in self.params.iter().filter(|param| param.required) {
+            if !params.map_has_entry(param.name).unwrap_or(false) {
+                problems.push(format!("Missing required parameter `{}`", param.name));
+            }
+        }
+        problems
+    }
+
+    /// Validate `params`, appending a cause attributed to `actor` for each problem, and return
+    /// a copy with the defaults of missing parameters filled in.
+    pub fn apply(
+        &self,
+        actor: &mut impl crate::current_thread::ErrorActor,
+        params: &BtValueConst,
+    ) -> Result<BtValueOwned, BtComponentClassInitializeMethodStatus> {
+        let mut problems = self.validate(params);
+        // The fields of a parameter can be set without `Param::default`
+        for param in self.params {
+            match param.default {
+                Some(default) if !accepts_default(param.value_type, default) => {
+                    problems.push(format!(
+                        "The default of parameter `{}` is not a {}",
+                        param.name,
+                        type_name(param.value_type)
+                    ))
+                }
+                _ => {}
+            }
+        }
+        if !problems.is_empty() {
+            for problem in problems {
+                actor.append_error_cause(file!(), u64::from(line!()), &problem);
+            }
+            return Err(BtComponentClassInitializeMethodStatus::Error);
+        }
+        let mut params = params
+            .copy()
+            .map_err(|_| BtComponentClassInitializeMethodStatus::MemoryError)?;
+        for param in self.params {
+            if let Some(default) = param.default {
+                if !params.map_has_entry(param.name).unwrap_or(true) {
+                    default
+                        .to_value()
+                        .and_then(|default| params.try_map_insert_entry(param.name, &default))
+                        .map_err(|error| match error.kind() {
+                            ErrorKind::MemoryError => {
+                                BtComponentClassInitializeMethodStatus::MemoryError
+                            }
+                            _ => BtComponentClassInitializeMethodStatus::Error,
+                        })?;
+                }
+            }
+        }
+        Ok(params)
+    }
+}
+
+/// Validate `params` with `schema` if there is one, and call `initialize` with the parameters
+/// to use
+pub(crate) fn initialize_with<A, T, F>(
+    schema: Option<ParamSchema>,
+    actor: &mut A,
+    params: &BtValueConst,
+    initialize: F,
+) -> Result<T, BtComponentClassInitializeMethodStatus>
+where
+    A: crate::current_thread::ErrorActor,
+    F: FnOnce(&mut A, &BtValueConst) -> Result<T, BtComponentClassInitializeMethodStatus>,
+{
+    match schema {
+        Some(schema) => {
+            let params = schema.apply(actor, params)?;
+            initialize(actor, &BtValueConst::from_ptr(params.as_ptr()))
+        }
+        None => initialize(actor, params),
+    }
+}
+
+/// Set the description and help text of a component class from `schema`
+pub(crate) fn set_help(
+    component_class: &mut BtComponentClass,
+    schema: &ParamSchema,
+) -> crate::error::Result<()> {
+    component_class.try_set_description(schema.description)?;
+    component_class.try_set_help(&schema.help())
+}
@@ -0,0 +1,381 @@
+// Copyright 2023 - 2023, Karsten Becker and the rust babeltrace2-plugin contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+//! Rust types as event payloads
+//!
+//! A [`BtPayload`] describes its field class as a [`FieldClassSpec`], and writes itself into a
+//! field of that class. It is implemented for integers, reals, `bool`, strings, arrays,
+//! `Vec` and `Option`, and can be derived for structs and enums:
+//!
+//! ```ignore
+//! #[derive(BtPayload)]
+//! struct Sample {
+//!     #[bt(display_base = "hexadecimal")]
+//!     address: u32,
+//!     #[bt(rename = "cpu_id")]
+//!     cpu: u8,
+//!     #[bt(skip)]
+//!     cache: Vec<u8>,
+//!     name: String,
+//! }
+//!
+//! let payload_class = Sample::create_field_class(&trace_class)?;
+//! event_class.try_set_payload_field_class(&payload_class)?;
+//! // ...
+//! sample.write_to(&mut event.borrow_payload_field())?;
+//! ```
+//!
+//! Structs become structures, with a member per field, named after the field or its index.
+//! Enums without fields become signed enumerations, with a mapping per variant to its
+//! discriminant. Other enums become variants without selector field, with an option per
+//! variant, holding a structure of the fields of the variant.
+//!
+//! The other way round, a [`FromBtField`] is read from a field. It is implemented for the same
+//! types, and its derive reads structures by member name, enumerations by mapping label and
+//! variants by option name. Skipped fields are set to their default.
+//!
+//! ```ignore
+//! #[derive(FromBtField)]
+//! struct Sample {
+//!     address: u32,
+//!     #[bt(rename = "cpu_id")]
+//!     cpu: u8,
+//!     name: String,
+//! }
+//!
+//! let sample = Sample::from_bt_field(&event.borrow_payload_field_const())?;
+//! ```
+use crate::bt2::*;
+use crate::error::{Error, ErrorKind, Result};
+use crate::field_class::{BuildResult, FieldClassHandle, FieldClassSpec, OptionSelector};
+
+pub use babeltrace2_plugin_derive::{BtPayload, FromBtField};
+
+/// The status of the errors of [`FromBtField`]
+const FROM_BT_FIELD: &str = "FromBtField";
+
+/// A value which can be written into a field
+pub trait BtPayload {
+    /// The class of the fields the value is written into
+    fn field_class_spec() -> FieldClassSpec;
+
+    /// Write the value into `field`, which has the class of [`BtPayload::field_class_spec`]
+    fn write_to(&self, field: &mut BtFieldRef<'_>) -> Result<()>;
+
+    /// Create the class of the fields in `trace_class`
+    fn create_field_class(trace_class: &BtTraceClass) -> BuildResult<FieldClassHandle> {
+        Self::field_class_spec().build(trace_class)
+    }
+}
+
+/// A [`BtPayload`] whose field class is an integer or an enumeration, which have a preferred
+/// display base. The derive only accepts `#[bt(display_base = ..)]` for fields of these types.
+pub trait BtIntegerPayload: BtPayload {
+    /// The class of the fields, with `display_base` as preferred display base
+    fn field_class_spec_with_display_base(
+        display_base: BtFieldClassIntegerPreferredDisplayBase,
+    ) -> FieldClassSpec {
+        Self::field_class_spec().display_base(display_base)
+    }
+}
+
+macro_rules! impl_bt_payload_for_integer {
+    ($($type:ty => $spec:ident, $set_value:ident, $value_type:ty, $bits:expr);*) => {
+        $(
+            impl BtPayload for $type {
+                fn field_class_spec() -> FieldClassSpec {
+                    FieldClassSpec::$spec($bits)
+                }
+
+                fn write_to(&self, field: &mut BtFieldRef<'_>) -> Result<()> {
+                    field.$set_value(*self as $value_type);
+                    Ok(())
+                }
+            }
+
+            impl BtIntegerPayload for $type {}
+        )*
+    };
+}
+
+impl_bt_payload_for_integer!(
+    u8 => unsigned_integer, integer_unsigned_set_value, u64, 8;
+    u16 => unsigned_integer, integer_unsigned_set_value, u64, 16;
+    u32 => unsigned_integer, integer_unsigned_set_value, u64, 32;
+    u64 => unsigned_integer, integer_unsigned_set_value, u64, 64;
+    usize => unsigned_integer, integer_unsigned_set_value, u64, usize::BITS.into();
+    i8 => signed_integer, integer_signed_set_value, i64, 8;
+    i16 => signed_integer, integer_signed_set_value, i64, 16;
+    i32 => signed_integer, integer_signed_set_value, i64, 32;
+    i64 => signed_integer, integer_signed_set_value, i64, 64;
+    isize => signed_integer, integer_signed_set_value, i64, isize::BITS.into()
+);
+
+impl BtPayload for bool {
+    fn field_class_spec() -> FieldClassSpec {
+        FieldClassSpec::Bool
+    }
+
+    fn write_to(&self, field: &mut BtFieldRef<'_>) -> Result<()> {
+        field.bool_set_value(*self);
+        Ok(())
+    }
+}
+
+impl BtPayload for f32 {
+    fn field_class_spec() -> FieldClassSpec {
+        FieldClassSpec::SinglePrecisionReal
+    }
+
+    fn write_to(&self, field: &mut BtFieldRef<'_>) -> Result<()> {
+        field.real_single_precision_set_value(*self);
+        Ok(())
+    }
+}
+
+impl BtPayload for f64 {
+    fn field_class_spec() -> FieldClassSpec {
+        FieldClassSpec::DoublePrecisionReal
+    }
+
+    fn write_to(&self, field: &mut BtFieldRef<'_>) -> Result<()> {
+        field.real_double_precision_set_value(*self);
+        Ok(())
+    }
+}
+
+impl BtPayload for str {
+    fn field_class_spec() -> FieldClassSpec {
+        FieldClassSpec::String
+    }
+
+    fn write_to(&self, field: &mut BtFieldRef<'_>) -> Result<()> {
+        field.try_string_set_value(self)
+    }
+}
+
+impl BtPayload for String {
+    fn field_class_spec() -> FieldClassSpec {
+        FieldClassSpec::String
+    }
+
+    fn write_to(&self, field: &mut BtFieldRef<'_>) -> Result<()> {
+        field.try_string_set_value(self)
+    }
+}
+
+impl<T: BtPayload + ?Sized> BtPayload for &T {
+    fn field_class_spec() -> FieldClassSpec {
+        T::field_class_spec()
+    }
+
+    fn write_to(&self, field: &mut BtFieldRef<'_>) -> Result<()> {
+        (**self).write_to(field)
+    }
+}
+
+impl<T: BtPayload + ?Sized> BtPayload for Box<T> {
+    fn field_class_spec() -> FieldClassSpec {
+        T::field_class_spec()
+    }
+
+    fn write_to(&self, field: &mut BtFieldRef<'_>) -> Result<()> {
+        (**self).write_to(field)
+    }
+}
+
+impl<T: BtIntegerPayload + ?Sized> BtIntegerPayload for &T {}
+
+impl<T: BtIntegerPayload + ?Sized> BtIntegerPayload for Box<T> {}
+
+/// Write `elements` into the array `field`, which already has their number as length
+fn write_elements<T: BtPayload>(elements: &[T], field: &mut BtFieldRef<'_>) -> Result<()> {
+    for (index, element) in (0u64..).zip(elements) {
+        element.write_to(&mut field.array_borrow_element_field_by_index(index))?;
+    }
+    Ok(())
+}
+
+/// A dynamic array without length field
+impl<T: BtPayload> BtPayload for [T] {
+    fn field_class_spec() -> FieldClassSpec {
+        FieldClassSpec::dynamic_array(T::field_class_spec(), None)
+    }
+
+    fn write_to(&self, field: &mut BtFieldRef<'_>) -> Result<()> {
+        field.try_array_dynamic_set_length(self.len() as u64)?;
+        write_elements(self, field)
+    }
+}
+
+/// A dynamic array without length field
+impl<T: BtPayload> BtPayload for Vec<T> {
+    fn field_class_spec() -> FieldClassSpec {
+        <[T]>::field_class_spec()
+    }
+
+    fn write_to(&self, field: &mut BtFieldRef<'_>) -> Result<()> {
+        self.as_slice().write_to(field)
+    }
+}
+
+impl<T: BtPayload, const N: usize> BtPayload for [T; N] {
+    fn field_class_spec() -> FieldClassSpec {
+        FieldClassSpec::static_array(T::field_class_spec(), N as u64)
+    }
+
+    fn write_to(&self, field: &mut BtFieldRef<'_>) -> Result<()> {
+        write_elements(self, field)
+    }
+}
+
+/// An option without selector field
+impl<T: BtPayload> BtPayload for Option<T> {
+    fn field_class_spec() -> FieldClassSpec {
+        FieldClassSpec::option(T::field_class_spec(), OptionSelector::None)
+    }
+
+    fn write_to(&self, field: &mut BtFieldRef<'_>) -> Result<()> {
+        field.option_set_has_field(self.is_some());
+        match self {
+            Some(value) => value.write_to(&mut field.option_borrow_field()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A value which can be read from a field
+pub trait FromBtField: Sized {
+    /// Read the value from `field`. Fails with [`ErrorKind::TypeMismatch`] when `field` does
+    /// not have a matching class type, [`ErrorKind::NotFound`] for missing structure members,
+    /// [`ErrorKind::NoMatch`] for unknown labels or options, and [`ErrorKind::OverflowError`]
+    /// for integers out of range.
+    fn from_bt_field(field: &BtFieldConst) -> Result<Self>;
+}
+
+/// Check that `field` has one of the class types `expected`
+pub fn check_class_type(field: &BtFieldConst, expected: &[BtFieldClassType]) -> Result<()> {
+    if expected.contains(&field.get_class_type()) {
+        Ok(())
+    } else {
+        Err(Error::new(ErrorKind::TypeMismatch, FROM_BT_FIELD))
+    }
+}
+
+/// Borrow the member `name` of the structure `field`
+pub fn borrow_member<'a>(field: &'a BtFieldConst, name: &str) -> Result<BtFieldConstRef<'a>> {
+    check_class_type(field, &[BtFieldClassType::Structure])?;
+    let member = field.structure_borrow_member_field_by_name_const(name)?;
+    if member.as_ptr().is_null() {
+        Err(Error::new(ErrorKind::NotFound, FROM_BT_FIELD))
+    } else {
+        Ok(member)
+    }
+}
+
+macro_rules! impl_from_bt_field_for_integer {
+    ($($type:ty => $get_value:ident, $integer:ident, $enumeration:ident);*) => {
+        $(
+            impl FromBtField for $type {
+                fn from_bt_field(field: &BtFieldConst) -> Result<Self> {
+                    check_class_type(
+                        field,
+                        &[BtFieldClassType::$integer, BtFieldClassType::$enumeration],
+                    )?;
+                    <$type>::try_from(field.$get_value())
+                        .map_err(|_| Error::new(ErrorKind::OverflowError, FROM_BT_FIELD))
+                }
+            }
+        )*
+    };
+}
+
+impl_from_bt_field_for_integer!(
+    u8 => integer_unsigned_get_value, UnsignedInteger, UnsignedEnumeration;
+    u16 => integer_unsigned_get_value, UnsignedInteger, UnsignedEnumeration;
+    u32 => integer_unsigned_get_value, UnsignedInteger, UnsignedEnumeration;
+    u64 => integer_unsigned_get_value, UnsignedInteger, UnsignedEnumeration;
+    usize => integer_unsigned_get_value, UnsignedInteger, UnsignedEnumeration;
+    i8 => integer_signed_get_value, SignedInteger, SignedEnumeration;
+    i16 => integer_signed_get_value, SignedInteger, SignedEnumeration;
+    i32 => integer_signed_get_value, SignedInteger, SignedEnumeration;
+    i64 => integer_signed_get_value, SignedInteger, SignedEnumeration;
+    isize => integer_signed_get_value, SignedInteger, SignedEnumeration
+);
+
+impl FromBtField for bool {
+    fn from_bt_field(field: &BtFieldConst) -> Result<Self> {
+        check_class_type(field, &[BtFieldClassType::Bool])?;
+        Ok(field.bool_get_value())
+    }
+}
+
+impl FromBtField for f32 {
+    fn from_bt_field(field: &BtFieldConst) -> Result<Self> {
+        check_class_type(field, &[BtFieldClassType::SinglePrecisionReal])?;
+        Ok(field.real_single_precision_get_value())
+    }
+}
+
+impl FromBtField for f64 {
+    fn from_bt_field(field: &BtFieldConst) -> Result<Self> {
+        match field.get_class_type() {
+            BtFieldClassType::SinglePrecisionReal => Ok(field.real_single_precision_get_value().into()),
+            _ => {
+                check_class_type(field, &[BtFieldClassType::DoublePrecisionReal])?;
+                Ok(field.real_double_precision_get_value())
+            }
+        }
+    }
+}
+
+/// Invalid UTF-8 sequences are replaced, as the strings of traces are not always UTF-8
+impl FromBtField for String {
+    fn from_bt_field(field: &BtFieldConst) -> Result<Self> {
+        check_class_type(field, &[BtFieldClassType::String])?;
+        Ok(field.string_get_value().to_string_lossy().into_owned())
+    }
+}
+
+impl<T: FromBtField> FromBtField for Box<T> {
+    fn from_bt_field(field: &BtFieldConst) -> Result<Self> {
+        T::from_bt_field(field).map(Box::new)
+    }
+}
+
+/// A static or dynamic array
+impl<T: FromBtField> FromBtField for Vec<T> {
+    fn from_bt_field(field: &BtFieldConst) -> Result<Self> {
+        check_class_type(
+            field,
+            &[
+                BtFieldClassType::StaticArray,
+                BtFieldClassType::DynamicArrayWithoutLengthField,
+                BtFieldClassType::DynamicArrayWithLengthField,
+            ],
+        )?;
+        (0..field.array_get_length())
+            .map(|index| T::from_bt_field(&field.array_borrow_element_field_by_index_const(index)))
+            .collect()
+    }
+}
+
+/// An option, with or without selector field
+impl<T: FromBtField> FromBtField for Option<T> {
+    fn from_bt_field(field: &BtFieldConst) -> Result<Self> {
+        check_class_type(
+            field,
+            &[
+                BtFieldClassType::OptionWithoutSelectorField,
+                BtFieldClassType::OptionWithBoolSelectorField,
+                BtFieldClassType::OptionWithUnsignedIntegerSelectorField,
+                BtFieldClassType::OptionWithSignedIntegerSelectorField,
+            ],
+        )?;
+        let option = field.option_borrow_field_const();
+        if option.as_ptr().is_null() {
+            Ok(None)
+        } else {
+            T::from_bt_field(&option).map(Some)
+        }
+    }
+}
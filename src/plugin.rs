@@ -0,0 +1,600 @@
+// Copyright 2023 - 2023, Karsten Becker and the rust babeltrace2-plugin contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+//! Plugin and component class descriptors for the babeltrace2 shared object loader
+//!
+//! libbabeltrace2 discovers the content of a plugin shared object by reading four linker
+//! sections, which the C macros `BT_PLUGIN_MODULE()`, `BT_PLUGIN()` and friends of
+//! `babeltrace2/plugin/plugin-dev.h` fill. The structures in this module mirror the packed
+//! C structures of that header and the [`bt_plugin!`](crate::bt_plugin) macro emits them
+//! into the very same sections, so a `cdylib` is directly loadable with
+//! `babeltrace2 --plugin-path`.
+use std::os::raw::c_char;
+
+use crate::bt2::binding::*;
+use crate::bt2::BtComponentClassType;
+
+/// Major version of the plugin descriptor interface (`__BT_PLUGIN_VERSION_MAJOR`)
+pub const PLUGIN_VERSION_MAJOR: u32 = 1;
+/// Minor version of the plugin descriptor interface (`__BT_PLUGIN_VERSION_MINOR`)
+pub const PLUGIN_VERSION_MINOR: u32 = 0;
+
+/// A pointer stored in one of the plugin sections. The loader skips null entries.
+#[repr(transparent)]
+pub struct SectionEntry<T>(pub *const T);
+unsafe impl<T> Sync for SectionEntry<T> {}
+impl<T> SectionEntry<T> {
+    /// An empty entry, used to make sure a section exists even if nothing else is put into it.
+    pub const fn null() -> SectionEntry<T> {
+        SectionEntry(std::ptr::null())
+    }
+}
+
+/// Mirrors `struct __bt_plugin_descriptor`
+#[repr(C, packed)]
+pub struct PluginDescriptor {
+    pub major: u32,
+    pub minor: u32,
+    pub name: *const c_char,
+}
+unsafe impl Sync for PluginDescriptor {}
+impl PluginDescriptor {
+    /// Create a descriptor for the plugin `name`, which has to be NUL terminated.
+    pub const fn new(name: &'static str) -> PluginDescriptor {
+        PluginDescriptor {
+            major: PLUGIN_VERSION_MAJOR,
+            minor: PLUGIN_VERSION_MINOR,
+            name: name.as_ptr() as *const c_char,
+        }
+    }
+}
+
+/// Mirrors `enum __bt_plugin_descriptor_attribute_type`
+#[repr(C)]
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum PluginAttributeType {
+    Initialize = 0,
+    Finalize = 1,
+    Author = 2,
+    License = 3,
+    Description = 4,
+    Version = 5,
+}
+
+/// Mirrors `struct __bt_plugin_descriptor_version`
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct PluginVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+    pub extra: *const c_char,
+}
+
+/// Mirrors the value union of `struct __bt_plugin_descriptor_attribute`
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union PluginAttributeValue {
+    pub initialize: bt_plugin_initialize_func,
+    pub finalize: bt_plugin_finalize_func,
+    pub string: *const c_char,
+    pub version: PluginVersion,
+}
+
+/// Mirrors `struct __bt_plugin_descriptor_attribute`
+#[repr(C, packed)]
+pub struct PluginAttribute {
+    pub descriptor: *const PluginDescriptor,
+    pub type_name: *const c_char,
+    pub attribute_type: PluginAttributeType,
+    pub value: PluginAttributeValue,
+}
+unsafe impl Sync for PluginAttribute {}
+impl PluginAttribute {
+    /// Create an attribute of `descriptor`. `type_name` has to be NUL terminated.
+    pub const fn new(
+        descriptor: &'static PluginDescriptor,
+        type_name: &'static str,
+        attribute_type: PluginAttributeType,
+        value: PluginAttributeValue,
+    ) -> PluginAttribute {
+        PluginAttribute {
+            descriptor,
+            type_name: type_name.as_ptr() as *const c_char,
+            attribute_type,
+            value,
+        }
+    }
+}
+
+/// Mirrors the methods union of `struct __bt_plugin_component_class_descriptor`
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union ComponentClassMethods {
+    pub message_iterator_next: bt_message_iterator_class_next_method,
+    pub consume: bt_component_class_sink_consume_method,
+}
+
+/// Mirrors `struct __bt_plugin_component_class_descriptor`
+#[repr(C, packed)]
+pub struct ComponentClassDescriptor {
+    pub plugin_descriptor: *const PluginDescriptor,
+    pub name: *const c_char,
+    pub component_class_type: BtComponentClassType,
+    pub methods: ComponentClassMethods,
+}
+unsafe impl Sync for ComponentClassDescriptor {}
+impl ComponentClassDescriptor {
+    /// Create a component class descriptor of the plugin `plugin_descriptor`. `name` has to
+    /// be NUL terminated.
+    pub const fn new(
+        plugin_descriptor: &'static PluginDescriptor,
+        name: &'static str,
+        component_class_type: BtComponentClassType,
+        methods: ComponentClassMethods,
+    ) -> ComponentClassDescriptor {
+        ComponentClassDescriptor {
+            plugin_descriptor,
+            name: name.as_ptr() as *const c_char,
+            component_class_type,
+            methods,
+        }
+    }
+}
+
+/// Mirrors `enum __bt_plugin_component_class_descriptor_attribute_type`
+#[repr(C)]
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum ComponentClassAttributeType {
+    Description = 0,
+    Help = 1,
+    GetSupportedMipVersionsMethod = 2,
+    InitializeMethod = 3,
+    FinalizeMethod = 4,
+    QueryMethod = 5,
+    InputPortConnectedMethod = 6,
+    OutputPortConnectedMethod = 7,
+    GraphIsConfiguredMethod = 8,
+    MessageIteratorInitializeMethod = 9,
+    MessageIteratorFinalizeMethod = 10,
+    MessageIteratorSeekNsFromOriginMethod = 11,
+    MessageIteratorSeekBeginningMethod = 12,
+    MessageIteratorCanSeekNsFromOriginMethod = 13,
+    MessageIteratorCanSeekBeginningMethod = 14,
+}
+
+/// Mirrors the value union of `struct __bt_plugin_component_class_descriptor_attribute`
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union ComponentClassAttributeValue {
+    pub string: *const c_char,
+    pub source_get_supported_mip_versions: bt_component_class_source_get_supported_mip_versions_method,
+    pub filter_get_supported_mip_versions: bt_component_class_filter_get_supported_mip_versions_method,
+    pub sink_get_supported_mip_versions: bt_component_class_sink_get_supported_mip_versions_method,
+    pub source_initialize: bt_component_class_source_initialize_method,
+    pub filter_initialize: bt_component_class_filter_initialize_method,
+    pub sink_initialize: bt_component_class_sink_initialize_method,
+    pub source_finalize: bt_component_class_source_finalize_method,
+    pub filter_finalize: bt_component_class_filter_finalize_method,
+    pub sink_finalize: bt_component_class_sink_finalize_method,
+    pub source_query: bt_component_class_source_query_method,
+    pub filter_query: bt_component_class_filter_query_method,
+    pub sink_query: bt_component_class_sink_query_method,
+    pub filter_input_port_connected: bt_component_class_filter_input_port_connected_method,
+    pub sink_input_port_connected: bt_component_class_sink_input_port_connected_method,
+    pub source_output_port_connected: bt_component_class_source_output_port_connected_method,
+    pub filter_output_port_connected: bt_component_class_filter_output_port_connected_method,
+    pub sink_graph_is_configured: bt_component_class_sink_graph_is_configured_method,
+    pub message_iterator_initialize: bt_message_iterator_class_initialize_method,
+    pub message_iterator_finalize: bt_message_iterator_class_finalize_method,
+    pub message_iterator_seek_ns_from_origin: bt_message_iterator_class_seek_ns_from_origin_method,
+    pub message_iterator_seek_beginning: bt_message_iterator_class_seek_beginning_method,
+    pub message_iterator_can_seek_ns_from_origin: bt_message_iterator_class_can_seek_ns_from_origin_method,
+    pub message_iterator_can_seek_beginning: bt_message_iterator_class_can_seek_beginning_method,
+}
+
+/// Mirrors `struct __bt_plugin_component_class_descriptor_attribute`
+#[repr(C, packed)]
+pub struct ComponentClassAttribute {
+    pub component_class_descriptor: *const ComponentClassDescriptor,
+    pub type_name: *const c_char,
+    pub attribute_type: ComponentClassAttributeType,
+    pub value: ComponentClassAttributeValue,
+}
+unsafe impl Sync for ComponentClassAttribute {}
+impl ComponentClassAttribute {
+    /// Create an attribute of `component_class_descriptor`. `type_name` has to be NUL terminated.
+    pub const fn new(
+        component_class_descriptor: &'static ComponentClassDescriptor,
+        type_name: &'static str,
+        attribute_type: ComponentClassAttributeType,
+        value: ComponentClassAttributeValue,
+    ) -> ComponentClassAttribute {
+        ComponentClassAttribute {
+            component_class_descriptor,
+            type_name: type_name.as_ptr() as *const c_char,
+            attribute_type,
+            value,
+        }
+    }
+}
+
+/// Declares a babeltrace2 plugin and its component classes.
+///
+/// This is the equivalent of the C macros `BT_PLUGIN_MODULE()`, `BT_PLUGIN()`,
+/// `BT_PLUGIN_DESCRIPTION()`, ..., `BT_PLUGIN_SOURCE_COMPONENT_CLASS()` and so on. It has to
+/// be invoked exactly once in a `cdylib` crate. The plugin attributes are optional, but have
+/// to be given in the order shown below. They are followed by the component classes, sources
+/// first, then filters and sinks last. Each component class is introduced by its kind
+/// (`source`, `filter` or `sink`) and its name, followed by its mandatory method (`next` for
+/// the message iterator of sources and filters, `consume` for sinks) and any number of
+/// optional attributes:
+///
+/// * `description`, `help`: string literals, the `PARAMS` schema of the component trait is not
+///   used for them
+/// * `get_supported_mip_versions`, `initialize`, `finalize`, `query`
+/// * `input_port_connected` (filter and sink), `output_port_connected` (source and filter)
+/// * `graph_is_configured` (sink)
+/// * `message_iterator_initialize`, `message_iterator_finalize`,
+///   `message_iterator_seek_beginning`, `message_iterator_can_seek_beginning`,
+///   `message_iterator_seek_ns_from_origin`, `message_iterator_can_seek_ns_from_origin`
+///   (source and filter)
+///
+/// The methods are `extern "C"` functions with the signature of the corresponding
+/// `bt_component_class_*_method` or `bt_message_iterator_class_*_method` type.
+///
+/// ```ignore
+/// babeltrace2_plugin::bt_plugin! {
+///     name: "epitome",
+///     description: "My first plugin",
+///     author: "Jane Doe",
+///     license: "MIT",
+///     version: (1, 0, 0, "rc1"),
+///     sink "output" {
+///         consume: epitome_out_consume,
+///         description: "Prints messages",
+///         initialize: epitome_out_initialize,
+///         finalize: epitome_out_finalize,
+///         graph_is_configured: epitome_out_graph_is_configured,
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! bt_plugin {
+    (
+        name: $name:literal,
+        $(description: $description:literal,)?
+        $(author: $author:literal,)?
+        $(license: $license:literal,)?
+        $(version: ($major:literal, $minor:literal, $patch:literal $(, $extra:literal)?),)?
+        $(initialize: $initialize:expr,)?
+        $(finalize: $finalize:expr,)?
+        $(source $source_name:literal {
+            next: $source_next:expr
+            $(, $source_attr:ident: $source_attr_value:expr)* $(,)?
+        })*
+        $(filter $filter_name:literal {
+            next: $filter_next:expr
+            $(, $filter_attr:ident: $filter_attr_value:expr)* $(,)?
+        })*
+        $(sink $sink_name:literal {
+            consume: $sink_consume:expr
+            $(, $sink_attr:ident: $sink_attr_value:expr)* $(,)?
+        })*
+    ) => {
+        const _: () = {
+            use $crate::plugin::*;
+
+            extern "C" {
+                static __start___bt_plugin_descriptors: SectionEntry<PluginDescriptor>;
+                static __stop___bt_plugin_descriptors: SectionEntry<PluginDescriptor>;
+                static __start___bt_plugin_descriptor_attributes: SectionEntry<PluginAttribute>;
+                static __stop___bt_plugin_descriptor_attributes: SectionEntry<PluginAttribute>;
+                static __start___bt_plugin_component_class_descriptors: SectionEntry<ComponentClassDescriptor>;
+                static __stop___bt_plugin_component_class_descriptors: SectionEntry<ComponentClassDescriptor>;
+                static __start___bt_plugin_component_class_descriptor_attributes: SectionEntry<ComponentClassAttribute>;
+                static __stop___bt_plugin_component_class_descriptor_attributes: SectionEntry<ComponentClassAttribute>;
+            }
+
+            #[no_mangle]
+            extern "C" fn __bt_get_begin_section_plugin_descriptors() -> *const SectionEntry<PluginDescriptor> {
+                unsafe { ::std::ptr::addr_of!(__start___bt_plugin_descriptors) }
+            }
+            #[no_mangle]
+            extern "C" fn __bt_get_end_section_plugin_descriptors() -> *const SectionEntry<PluginDescriptor> {
+                unsafe { ::std::ptr::addr_of!(__stop___bt_plugin_descriptors) }
+            }
+            #[no_mangle]
+            extern "C" fn __bt_get_begin_section_plugin_descriptor_attributes() -> *const SectionEntry<PluginAttribute> {
+                unsafe { ::std::ptr::addr_of!(__start___bt_plugin_descriptor_attributes) }
+            }
+            #[no_mangle]
+            extern "C" fn __bt_get_end_section_plugin_descriptor_attributes() -> *const SectionEntry<PluginAttribute> {
+                unsafe { ::std::ptr::addr_of!(__stop___bt_plugin_descriptor_attributes) }
+            }
+            #[no_mangle]
+            extern "C" fn __bt_get_begin_section_component_class_descriptors() -> *const SectionEntry<ComponentClassDescriptor> {
+                unsafe { ::std::ptr::addr_of!(__start___bt_plugin_component_class_descriptors) }
+            }
+            #[no_mangle]
+            extern "C" fn __bt_get_end_section_component_class_descriptors() -> *const SectionEntry<ComponentClassDescriptor> {
+                unsafe { ::std::ptr::addr_of!(__stop___bt_plugin_component_class_descriptors) }
+            }
+            #[no_mangle]
+            extern "C" fn __bt_get_begin_section_component_class_descriptor_attributes() -> *const SectionEntry<ComponentClassAttribute> {
+                unsafe { ::std::ptr::addr_of!(__start___bt_plugin_component_class_descriptor_attributes) }
+            }
+            #[no_mangle]
+            extern "C" fn __bt_get_end_section_component_class_descriptor_attributes() -> *const SectionEntry<ComponentClassAttribute> {
+                unsafe { ::std::ptr::addr_of!(__stop___bt_plugin_component_class_descriptor_attributes) }
+            }
+
+            // Null entries make sure every section exists, like BT_PLUGIN_MODULE() does
+            #[used]
+            #[link_section = "__bt_plugin_descriptors"]
+            static PLUGIN_DESCRIPTOR_DUMMY: SectionEntry<PluginDescriptor> = SectionEntry::null();
+            #[used]
+            #[link_section = "__bt_plugin_descriptor_attributes"]
+            static PLUGIN_ATTRIBUTE_DUMMY: SectionEntry<PluginAttribute> = SectionEntry::null();
+            #[used]
+            #[link_section = "__bt_plugin_component_class_descriptors"]
+            static COMPONENT_CLASS_DESCRIPTOR_DUMMY: SectionEntry<ComponentClassDescriptor> = SectionEntry::null();
+            #[used]
+            #[link_section = "__bt_plugin_component_class_descriptor_attributes"]
+            static COMPONENT_CLASS_ATTRIBUTE_DUMMY: SectionEntry<ComponentClassAttribute> = SectionEntry::null();
+
+            static PLUGIN_DESCRIPTOR: PluginDescriptor = PluginDescriptor::new(concat!($name, "\0"));
+            #[used]
+            #[link_section = "__bt_plugin_descriptors"]
+            static PLUGIN_DESCRIPTOR_ENTRY: SectionEntry<PluginDescriptor> = SectionEntry(&PLUGIN_DESCRIPTOR);
+
+            $(
+                static PLUGIN_DESCRIPTION: PluginAttribute = PluginAttribute::new(
+                    &PLUGIN_DESCRIPTOR,
+                    "description\0",
+                    PluginAttributeType::Description,
+                    PluginAttributeValue { string: concat!($description, "\0").as_ptr() as *const ::std::os::raw::c_char },
+                );
+                #[used]
+                #[link_section = "__bt_plugin_descriptor_attributes"]
+                static PLUGIN_DESCRIPTION_ENTRY: SectionEntry<PluginAttribute> = SectionEntry(&PLUGIN_DESCRIPTION);
+            )?
+            $(
+                static PLUGIN_AUTHOR: PluginAttribute = PluginAttribute::new(
+                    &PLUGIN_DESCRIPTOR,
+                    "author\0",
+                    PluginAttributeType::Author,
+                    PluginAttributeValue { string: concat!($author, "\0").as_ptr() as *const ::std::os::raw::c_char },
+                );
+                #[used]
+                #[link_section = "__bt_plugin_descriptor_attributes"]
+                static PLUGIN_AUTHOR_ENTRY: SectionEntry<PluginAttribute> = SectionEntry(&PLUGIN_AUTHOR);
+            )?
+            $(
+                static PLUGIN_LICENSE: PluginAttribute = PluginAttribute::new(
+                    &PLUGIN_DESCRIPTOR,
+                    "license\0",
+                    PluginAttributeType::License,
+                    PluginAttributeValue { string: concat!($license, "\0").as_ptr() as *const ::std::os::raw::c_char },
+                );
+                #[used]
+                #[link_section = "__bt_plugin_descriptor_attributes"]
+                static PLUGIN_LICENSE_ENTRY: SectionEntry<PluginAttribute> = SectionEntry(&PLUGIN_LICENSE);
+            )?
+            $(
+                static PLUGIN_VERSION: PluginAttribute = PluginAttribute::new(
+                    &PLUGIN_DESCRIPTOR,
+                    "version\0",
+                    PluginAttributeType::Version,
+                    PluginAttributeValue {
+                        version: PluginVersion {
+                            major: $major,
+                            minor: $minor,
+                            patch: $patch,
+                            extra: $crate::__bt_plugin_version_extra!($($extra)?),
+                        },
+                    },
+                );
+                #[used]
+                #[link_section = "__bt_plugin_descriptor_attributes"]
+                static PLUGIN_VERSION_ENTRY: SectionEntry<PluginAttribute> = SectionEntry(&PLUGIN_VERSION);
+            )?
+            $(
+                static PLUGIN_INITIALIZE: PluginAttribute = PluginAttribute::new(
+                    &PLUGIN_DESCRIPTOR,
+                    "init\0",
+                    PluginAttributeType::Initialize,
+                    PluginAttributeValue { initialize: Some($initialize) },
+                );
+                #[used]
+                #[link_section = "__bt_plugin_descriptor_attributes"]
+                static PLUGIN_INITIALIZE_ENTRY: SectionEntry<PluginAttribute> = SectionEntry(&PLUGIN_INITIALIZE);
+            )?
+            $(
+                static PLUGIN_FINALIZE: PluginAttribute = PluginAttribute::new(
+                    &PLUGIN_DESCRIPTOR,
+                    "exit\0",
+                    PluginAttributeType::Finalize,
+                    PluginAttributeValue { finalize: Some($finalize) },
+                );
+                #[used]
+                #[link_section = "__bt_plugin_descriptor_attributes"]
+                static PLUGIN_FINALIZE_ENTRY: SectionEntry<PluginAttribute> = SectionEntry(&PLUGIN_FINALIZE);
+            )?
+
+            $(
+                $crate::__bt_plugin_component_class!(
+                    Source source $source_name,
+                    ComponentClassMethods { message_iterator_next: Some($source_next) },
+                    [$($source_attr: $source_attr_value),*]
+                );
+            )*
+            $(
+                $crate::__bt_plugin_component_class!(
+                    Filter filter $filter_name,
+                    ComponentClassMethods { message_iterator_next: Some($filter_next) },
+                    [$($filter_attr: $filter_attr_value),*]
+                );
+            )*
+            $(
+                $crate::__bt_plugin_component_class!(
+                    Sink sink $sink_name,
+                    ComponentClassMethods { consume: Some($sink_consume) },
+                    [$($sink_attr: $sink_attr_value),*]
+                );
+            )*
+        };
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __bt_plugin_version_extra {
+    () => {
+        ::std::ptr::null()
+    };
+    ($extra:literal) => {
+        concat!($extra, "\0").as_ptr() as *const ::std::os::raw::c_char
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __bt_plugin_component_class {
+    ($type:ident $kind:ident $name:literal, $methods:expr, [$($attr:ident: $value:expr),*]) => {
+        const _: () = {
+            static COMPONENT_CLASS_DESCRIPTOR: ComponentClassDescriptor = ComponentClassDescriptor::new(
+                &PLUGIN_DESCRIPTOR,
+                concat!($name, "\0"),
+                $crate::bt2::BtComponentClassType::$type,
+                $methods,
+            );
+            #[used]
+            #[link_section = "__bt_plugin_component_class_descriptors"]
+            static COMPONENT_CLASS_DESCRIPTOR_ENTRY: SectionEntry<ComponentClassDescriptor> =
+                SectionEntry(&COMPONENT_CLASS_DESCRIPTOR);
+            #[used]
+            #[link_section = "__bt_plugin_component_class_descriptor_attributes"]
+            static COMPONENT_CLASS_ATTRIBUTE_ENTRIES: [SectionEntry<ComponentClassAttribute>;
+                <[&str]>::len(&[$(stringify!($attr)),*])] = [$(
+                SectionEntry({
+                    static ATTRIBUTE: ComponentClassAttribute =
+                        $crate::__bt_plugin_component_class_attribute!($kind $attr: $value);
+                    &ATTRIBUTE
+                })
+            ),*];
+        };
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __bt_plugin_component_class_attribute {
+    (@new $type_name:literal, $attribute_type:ident, $field:ident: $value:expr) => {
+        ComponentClassAttribute::new(
+            &COMPONENT_CLASS_DESCRIPTOR,
+            concat!($type_name, "\0"),
+            ComponentClassAttributeType::$attribute_type,
+            ComponentClassAttributeValue { $field: $value },
+        )
+    };
+    ($kind:ident description: $value:expr) => {
+        $crate::__bt_plugin_component_class_attribute!(@new "description", Description,
+            string: concat!($value, "\0").as_ptr() as *const ::std::os::raw::c_char)
+    };
+    ($kind:ident help: $value:expr) => {
+        $crate::__bt_plugin_component_class_attribute!(@new "help", Help,
+            string: concat!($value, "\0").as_ptr() as *const ::std::os::raw::c_char)
+    };
+    (source get_supported_mip_versions: $value:expr) => {
+        $crate::__bt_plugin_component_class_attribute!(@new "get supported MIP versions method",
+            GetSupportedMipVersionsMethod, source_get_supported_mip_versions: Some($value))
+    };
+    (filter get_supported_mip_versions: $value:expr) => {
+        $crate::__bt_plugin_component_class_attribute!(@new "get supported MIP versions method",
+            GetSupportedMipVersionsMethod, filter_get_supported_mip_versions: Some($value))
+    };
+    (sink get_supported_mip_versions: $value:expr) => {
+        $crate::__bt_plugin_component_class_attribute!(@new "get supported MIP versions method",
+            GetSupportedMipVersionsMethod, sink_get_supported_mip_versions: Some($value))
+    };
+    (source initialize: $value:expr) => {
+        $crate::__bt_plugin_component_class_attribute!(@new "initialize method",
+            InitializeMethod, source_initialize: Some($value))
+    };
+    (filter initialize: $value:expr) => {
+        $crate::__bt_plugin_component_class_attribute!(@new "initialize method",
+            InitializeMethod, filter_initialize: Some($value))
+    };
+    (sink initialize: $value:expr) => {
+        $crate::__bt_plugin_component_class_attribute!(@new "initialize method",
+            InitializeMethod, sink_initialize: Some($value))
+    };
+    (source finalize: $value:expr) => {
+        $crate::__bt_plugin_component_class_attribute!(@new "finalize method",
+            FinalizeMethod, source_finalize: Some($value))
+    };
+    (filter finalize: $value:expr) => {
+        $crate::__bt_plugin_component_class_attribute!(@new "finalize method",
+            FinalizeMethod, filter_finalize: Some($value))
+    };
+    (sink finalize: $value:expr) => {
+        $crate::__bt_plugin_component_class_attribute!(@new "finalize method",
+            FinalizeMethod, sink_finalize: Some($value))
+    };
+    (source query: $value:expr) => {
+        $crate::__bt_plugin_component_class_attribute!(@new "query method",
+            QueryMethod, source_query: Some($value))
+    };
+    (filter query: $value:expr) => {
+        $crate::__bt_plugin_component_class_attribute!(@new "query method",
+            QueryMethod, filter_query: Some($value))
+    };
+    (sink query: $value:expr) => {
+        $crate::__bt_plugin_component_class_attribute!(@new "query method",
+            QueryMethod, sink_query: Some($value))
+    };
+    (filter input_port_connected: $value:expr) => {
+        $crate::__bt_plugin_component_class_attribute!(@new "input port connected method",
+            InputPortConnectedMethod, filter_input_port_connected: Some($value))
+    };
+    (sink input_port_connected: $value:expr) => {
+        $crate::__bt_plugin_component_class_attribute!(@new "input port connected method",
+            InputPortConnectedMethod, sink_input_port_connected: Some($value))
+    };
+    (source output_port_connected: $value:expr) => {
+        $crate::__bt_plugin_component_class_attribute!(@new "output port connected method",
+            OutputPortConnectedMethod, source_output_port_connected: Some($value))
+    };
+    (filter output_port_connected: $value:expr) => {
+        $crate::__bt_plugin_component_class_attribute!(@new "output port connected method",
+            OutputPortConnectedMethod, filter_output_port_connected: Some($value))
+    };
+    (sink graph_is_configured: $value:expr) => {
+        $crate::__bt_plugin_component_class_attribute!(@new "graph is configured method",
+            GraphIsConfiguredMethod, sink_graph_is_configured: Some($value))
+    };
+    ($kind:ident message_iterator_initialize: $value:expr) => {
+        $crate::__bt_plugin_component_class_attribute!(@new "message iterator class initialize method",
+            MessageIteratorInitializeMethod, message_iterator_initialize: Some($value))
+    };
+    ($kind:ident message_iterator_finalize: $value:expr) => {
+        $crate::__bt_plugin_component_class_attribute!(@new "message iterator class finalize method",
+            MessageIteratorFinalizeMethod, message_iterator_finalize: Some($value))
+    };
+    ($kind:ident message_iterator_seek_ns_from_origin: $value:expr) => {
+        $crate::__bt_plugin_component_class_attribute!(@new "message iterator class seek NS from origin method",
+            MessageIteratorSeekNsFromOriginMethod, message_iterator_seek_ns_from_origin: Some($value))
+    };
+    ($kind:ident message_iterator_seek_beginning: $value:expr) => {
+        $crate::__bt_plugin_component_class_attribute!(@new "message iterator class seek beginning method",
+            MessageIteratorSeekBeginningMethod, message_iterator_seek_beginning: Some($value))
+    };
+    ($kind:ident message_iterator_can_seek_ns_from_origin: $value:expr) => {
+        $crate::__bt_plugin_component_class_attribute!(@new "message iterator class can seek NS from origin method",
+            MessageIteratorCanSeekNsFromOriginMethod, message_iterator_can_seek_ns_from_origin: Some($value))
+    };
+    ($kind:ident message_iterator_can_seek_beginning: $value:expr) => {
+        $crate::__bt_plugin_component_class_attribute!(@new "message iterator class can seek beginning method",
+            MessageIteratorCanSeekBeginningMethod, message_iterator_can_seek_beginning: Some($value))
+    };
+}
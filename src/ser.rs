@@ -0,0 +1,310 @@
+// Copyright 2023 - 2023, Karsten Becker and the rust babeltrace2-plugin contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+//! `serde::Serialize` for fields, events and event classes, with the `serde` feature
+//!
+//! Fields are serialized through their [`FieldValue`], by class type:
+//! * booleans, bit arrays, integers, reals and strings as the plain value
+//! * enumerations as a struct with the integer `value` and the `labels` of the mappings
+//!   containing it
+//! * structures as a map from member name to value, arrays as a sequence
+//! * options as an option, variants as a map with the selected option name as only key
+//!
+//! Events are structs with the `name` of their class and the `common_context`,
+//! `specific_context` and `payload` fields. Missing fields and names are `None`.
+use std::borrow::Cow;
+use std::ffi::CStr;
+
+use serde::ser::{Error, SerializeMap, SerializeStruct};
+use serde::{Serialize, Serializer};
+
+use crate::bt2::binding::*;
+use crate::bt2::*;
+use crate::field_value::FieldValue;
+use crate::payload::FromBtField;
+
+fn optional_field<'a>(field: &'a BtFieldConstRef<'_>) -> Option<&'a BtFieldConst> {
+    (!field.as_ptr().is_null()).then_some(&**field)
+}
+
+fn optional_field_class<'a>(
+    field_class: &'a BtFieldClassConstRef<'_>,
+) -> Option<&'a BtFieldClassConst> {
+    (!field_class.as_ptr().is_null()).then_some(&**field_class)
+}
+
+/// The value and labels of an enumeration field
+#[derive(Serialize)]
+struct Enumeration<'a> {
+    value: &'a FieldValue,
+    labels: &'a [String],
+}
+
+impl Serialize for FieldValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            FieldValue::Bool(value) => serializer.serialize_bool(*value),
+            FieldValue::BitArray { value, .. } | FieldValue::UInt(value) => {
+                serializer.serialize_u64(*value)
+            }
+            FieldValue::SInt(value) => serializer.serialize_i64(*value),
+            FieldValue::Real(value) => serializer.serialize_f64(*value),
+            FieldValue::String(value) => serializer.serialize_str(value),
+            FieldValue::Enum { value, labels } => {
+                Enumeration { value, labels }.serialize(serializer)
+            }
+            FieldValue::Array(elements) => serializer.collect_seq(elements),
+            FieldValue::Struct(members) => {
+                serializer.collect_map(members.iter().map(|(name, value)| (name, value)))
+            }
+            FieldValue::Option(value) => value.serialize(serializer),
+            FieldValue::Variant { option_name, value } => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry(option_name, value)?;
+                map.end()
+            }
+        }
+    }
+}
+
+/// Fields are read into a [`FieldValue`], which is serialized
+impl Serialize for BtFieldConst {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        FieldValue::from_bt_field(self)
+            .map_err(S::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
+impl Serialize for BtEventConst {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let class = self.borrow_class_const();
+        let mut event = serializer.serialize_struct("BtEvent", 4)?;
+        event.serialize_field(
+            "name",
+            &class.get_optional_name().map(CStr::to_string_lossy),
+        )?;
+        event.serialize_field(
+            "common_context",
+            &optional_field(&self.borrow_common_context_field_const()),
+        )?;
+        event.serialize_field(
+            "specific_context",
+            &optional_field(&self.borrow_specific_context_field_const()),
+        )?;
+        event.serialize_field(
+            "payload",
+            &optional_field(&self.borrow_payload_field_const()),
+        )?;
+        event.end()
+    }
+}
+
+impl Serialize for BtEventClassConst {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut event_class = serializer.serialize_struct("BtEventClass", 6)?;
+        event_class.serialize_field("id", &self.get_id())?;
+        event_class.serialize_field(
+            "name",
+            &self.get_optional_name().map(CStr::to_string_lossy),
+        )?;
+        event_class.serialize_field(
+            "log_level",
+            &self
+                .get_log_level()
+                .map(|log_level| format!("{log_level:?}")),
+        )?;
+        event_class.serialize_field(
+            "emf_uri",
+            &self.get_optional_emf_uri().map(CStr::to_string_lossy),
+        )?;
+        event_class.serialize_field(
+            "specific_context_field_class",
+            &optional_field_class(&self.borrow_specific_context_field_class_const()),
+        )?;
+        event_class.serialize_field(
+            "payload_field_class",
+            &optional_field_class(&self.borrow_payload_field_class_const()),
+        )?;
+        event_class.end()
+    }
+}
+
+/// A mapping of an enumeration field class
+#[derive(Serialize)]
+struct Mapping<'a, T> {
+    label: Cow<'a, str>,
+    ranges: Vec<(T, T)>,
+}
+
+/// A structure member or variant option
+#[derive(Serialize)]
+struct Named<'a> {
+    name: Cow<'a, str>,
+    field_class: BtFieldClassConstRef<'a>,
+}
+
+/// The label of an unsigned or signed mapping, the upcast is a pointer cast like in C
+fn mapping_label<'a, T>(mapping: *const T) -> Cow<'a, str> {
+    let label = unsafe {
+        CStr::from_ptr(bt_field_class_enumeration_mapping_get_label(
+            mapping as *const bt_field_class_enumeration_mapping,
+        ))
+    };
+    label.to_string_lossy()
+}
+
+/// The number of ranges of an unsigned or signed range set
+fn range_count<T>(range_set: *const T) -> u64 {
+    unsafe { bt_integer_range_set_get_range_count(range_set as *const bt_integer_range_set) }
+}
+
+/// Field classes are structs with their `type` and the properties of this type, the field
+/// classes they contain are serialized recursively.
+impl Serialize for BtFieldClassConst {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let class_type = self.get_type();
+        let field_count = match class_type {
+            BtFieldClassType::UnsignedEnumeration | BtFieldClassType::SignedEnumeration => 4,
+            BtFieldClassType::UnsignedInteger
+            | BtFieldClassType::SignedInteger
+            | BtFieldClassType::StaticArray => 3,
+            BtFieldClassType::BitArray
+            | BtFieldClassType::DynamicArrayWithoutLengthField
+            | BtFieldClassType::DynamicArrayWithLengthField
+            | BtFieldClassType::Structure
+            | BtFieldClassType::OptionWithoutSelectorField
+            | BtFieldClassType::OptionWithBoolSelectorField
+            | BtFieldClassType::OptionWithUnsignedIntegerSelectorField
+            | BtFieldClassType::OptionWithSignedIntegerSelectorField
+            | BtFieldClassType::VariantWithoutSelectorField
+            | BtFieldClassType::VariantWithUnsignedIntegerSelectorField
+            | BtFieldClassType::VariantWithSignedIntegerSelectorField => 2,
+            _ => 1,
+        };
+        let mut field_class = serializer.serialize_struct("BtFieldClass", field_count)?;
+        field_class.serialize_field("type", &format!("{class_type:?}"))?;
+        match class_type {
+            BtFieldClassType::BitArray => {
+                field_class.serialize_field("length", &self.bit_array_get_length())?
+            }
+            BtFieldClassType::UnsignedInteger
+            | BtFieldClassType::SignedInteger
+            | BtFieldClassType::UnsignedEnumeration
+            | BtFieldClassType::SignedEnumeration => {
+                field_class
+                    .serialize_field("field_value_range", &self.integer_get_field_value_range())?;
+                field_class.serialize_field(
+                    "preferred_display_base",
+                    &format!("{:?}", self.integer_get_preferred_display_base()),
+                )?;
+            }
+            _ => {}
+        }
+        match class_type {
+            BtFieldClassType::UnsignedEnumeration => {
+                let mappings: Vec<_> = (0..self.enumeration_get_mapping_count())
+                    .map(|index| {
+                        let mapping =
+                            self.enumeration_unsigned_borrow_mapping_by_index_const(index);
+                        let ranges = mapping.borrow_ranges_const();
+                        Mapping {
+                            label: mapping_label(mapping.as_ptr()),
+                            ranges: (0..range_count(ranges.as_ptr()))
+                                .map(|index| {
+                                    let range = ranges.borrow_range_by_index_const(index);
+                                    (range.get_lower(), range.get_upper())
+                                })
+                                .collect(),
+                        }
+                    })
+                    .collect();
+                field_class.serialize_field("mappings", &mappings)?;
+            }
+            BtFieldClassType::SignedEnumeration => {
+                let mappings: Vec<_> = (0..self.enumeration_get_mapping_count())
+                    .map(|index| {
+                        let mapping = self.enumeration_signed_borrow_mapping_by_index_const(index);
+                        let ranges = mapping.borrow_ranges_const();
+                        Mapping {
+                            label: mapping_label(mapping.as_ptr()),
+                            ranges: (0..range_count(ranges.as_ptr()))
+                                .map(|index| {
+                                    let range = ranges.borrow_range_by_index_const(index);
+                                    (range.get_lower(), range.get_upper())
+                                })
+                                .collect(),
+                        }
+                    })
+                    .collect();
+                field_class.serialize_field("mappings", &mappings)?;
+            }
+            BtFieldClassType::StaticArray
+            | BtFieldClassType::DynamicArrayWithoutLengthField
+            | BtFieldClassType::DynamicArrayWithLengthField => {
+                field_class.serialize_field(
+                    "element_field_class",
+                    &self.array_borrow_element_field_class_const(),
+                )?;
+                if class_type == BtFieldClassType::StaticArray {
+                    field_class.serialize_field("length", &self.array_static_get_length())?;
+                }
+            }
+            BtFieldClassType::Structure => {
+                let members: Vec<_> = (0..self.structure_get_member_count())
+                    .map(|index| self.structure_borrow_member_by_index_const(index))
+                    .collect();
+                let members: Vec<_> = members
+                    .iter()
+                    .map(|member| Named {
+                        name: member.get_name().to_string_lossy(),
+                        field_class: member.borrow_field_class_const(),
+                    })
+                    .collect();
+                field_class.serialize_field("members", &members)?;
+            }
+            BtFieldClassType::OptionWithoutSelectorField
+            | BtFieldClassType::OptionWithBoolSelectorField
+            | BtFieldClassType::OptionWithUnsignedIntegerSelectorField
+            | BtFieldClassType::OptionWithSignedIntegerSelectorField => {
+                field_class
+                    .serialize_field("field_class", &self.option_borrow_field_class_const())?;
+            }
+            BtFieldClassType::VariantWithoutSelectorField
+            | BtFieldClassType::VariantWithUnsignedIntegerSelectorField
+            | BtFieldClassType::VariantWithSignedIntegerSelectorField => {
+                let options: Vec<_> = (0..self.variant_get_option_count())
+                    .map(|index| self.variant_borrow_option_by_index_const(index))
+                    .collect();
+                let options: Vec<_> = options
+                    .iter()
+                    .map(|option| Named {
+                        name: option.get_name().to_string_lossy(),
+                        field_class: option.borrow_field_class_const(),
+                    })
+                    .collect();
+                field_class.serialize_field("options", &options)?;
+            }
+            _ => {}
+        }
+        field_class.end()
+    }
+}
+
+macro_rules! impl_serialize_for_ref {
+    ($($type:ident),*) => {
+        $(
+            impl Serialize for $type<'_> {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    (**self).serialize(serializer)
+                }
+            }
+        )*
+    };
+}
+
+impl_serialize_for_ref!(
+    BtFieldConstRef,
+    BtEventConstRef,
+    BtEventClassConstRef,
+    BtFieldClassConstRef
+);
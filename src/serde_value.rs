@@ -0,0 +1,677 @@
+// Copyright 2023 - 2023, Karsten Becker and the rust babeltrace2-plugin contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+//! `serde` for values, with the `serde` feature
+//!
+//! [`from_value`] deserializes Rust types from values, typically component parameters, and
+//! [`to_value`] creates values, typically query results, from Rust types:
+//!
+//! ```ignore
+//! #[derive(Deserialize)]
+//! struct Params {
+//!     path: String,
+//!     #[serde(default)]
+//!     verbose: bool,
+//! }
+//!
+//! let params: Params = from_value(params)?;
+//! ```
+//!
+//! Maps are structs or maps, arrays are sequences or tuples, and null is `None` or `()`. Enums
+//! are the name of the variant for unit variants, and a map from the name of the variant to
+//! its content otherwise.
+use std::ffi::CStr;
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::value::BorrowedStrDeserializer;
+use serde::de::{self, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::ser::{self, Serialize};
+use serde::Deserialize;
+
+use crate::bt2::binding::*;
+use crate::bt2::*;
+use crate::value::for_each_entry_ptr;
+
+/// An error of [`from_value`] or [`to_value`], with the map keys and array indexes leading to
+/// the value causing it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    path: Vec<String>,
+    message: String,
+}
+
+impl Error {
+    fn new(message: impl fmt::Display) -> Error {
+        Error {
+            path: Vec::new(),
+            message: message.to_string(),
+        }
+    }
+
+    fn in_key(mut self, key: &str) -> Error {
+        self.path.insert(0, key.to_string());
+        self
+    }
+
+    /// The map keys and array indexes leading to the value, outermost first
+    pub fn path(&self) -> &[String] {
+        &self.path
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path.join("."), self.message)
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(message: T) -> Error {
+        Error::new(message)
+    }
+}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(message: T) -> Error {
+        Error::new(message)
+    }
+}
+
+impl From<crate::error::Error> for Error {
+    fn from(error: crate::error::Error) -> Error {
+        Error::new(error)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Deserialize a `T` from `value`
+pub fn from_value<'de, T: Deserialize<'de>>(value: &'de BtValueConst) -> Result<T> {
+    T::deserialize(ValueDeserializer::new(value.as_ptr()))
+}
+
+/// The entries of the map `map`, they live as long as the map
+fn map_entries<'de>(map: *const bt_value) -> Vec<(&'de CStr, BtValueConst)> {
+    let mut entries = Vec::new();
+    let _ = for_each_entry_ptr(map, |key, value| {
+        entries.push((
+            unsafe { CStr::from_ptr(key) },
+            BtValueConst::from_ptr(value),
+        ));
+        Ok::<(), ()>(())
+    });
+    entries
+}
+
+fn to_str(string: &CStr) -> Result<&str> {
+    string
+        .to_str()
+        .map_err(|_| Error::new(format!("invalid UTF-8 in string {string:?}")))
+}
+
+struct ValueDeserializer<'de> {
+    value: BtValueConst,
+    phantom: PhantomData<&'de BtValueConst>,
+}
+
+impl<'de> ValueDeserializer<'de> {
+    fn new(value: *const bt_value) -> Self {
+        ValueDeserializer {
+            value: BtValueConst::from_ptr(value),
+            phantom: PhantomData,
+        }
+    }
+
+    fn string(&self) -> Result<&'de str> {
+        to_str(unsafe { CStr::from_ptr(bt_value_string_get(self.value.as_ptr())) })
+    }
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value.get_type() {
+            BtValueType::Null => visitor.visit_unit(),
+            BtValueType::Bool => visitor.visit_bool(self.value.bool_get()),
+            BtValueType::UnsignedInteger => visitor.visit_u64(self.value.integer_unsigned_get()),
+            BtValueType::SignedInteger => visitor.visit_i64(self.value.integer_signed_get()),
+            BtValueType::Real => visitor.visit_f64(self.value.real_get()),
+            BtValueType::String => visitor.visit_borrowed_str(self.string()?),
+            BtValueType::Array => visitor.visit_seq(ArrayAccess {
+                length: self.value.array_get_length(),
+                array: self.value,
+                index: 0,
+                phantom: PhantomData,
+            }),
+            BtValueType::Map => visitor.visit_map(MapAccess {
+                entries: map_entries(self.value.as_ptr()).into_iter(),
+                value: None,
+            }),
+            value_type => Err(Error::new(format!("unexpected value type {value_type:?}"))),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value.get_type() {
+            BtValueType::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        match self.value.get_type() {
+            BtValueType::String => {
+                visitor.visit_enum(IntoDeserializer::<Error>::into_deserializer(self.string()?))
+            }
+            BtValueType::Map => {
+                let mut entries = map_entries(self.value.as_ptr());
+                match entries.pop() {
+                    Some((variant, value)) if entries.is_empty() => {
+                        visitor.visit_enum(VariantAccess {
+                            variant: to_str(variant)?,
+                            value,
+                            phantom: PhantomData,
+                        })
+                    }
+                    _ => Err(Error::new(
+                        "expected a map with a single key for an enum variant",
+                    )),
+                }
+            }
+            _ => Err(Error::new(
+                "expected a string or a map with a single key for an enum",
+            )),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct ArrayAccess<'de> {
+    array: BtValueConst,
+    index: u64,
+    length: u64,
+    phantom: PhantomData<&'de BtValueConst>,
+}
+
+impl<'de> de::SeqAccess<'de> for ArrayAccess<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        if self.index == self.length {
+            return Ok(None);
+        }
+        let index = self.index;
+        self.index += 1;
+        let element = self
+            .array
+            .array_borrow_element_by_index_const(index)
+            .as_ptr();
+        seed.deserialize(ValueDeserializer::new(element))
+            .map(Some)
+            .map_err(|error| error.in_key(&index.to_string()))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some((self.length - self.index) as usize)
+    }
+}
+
+struct MapAccess<'de> {
+    entries: std::vec::IntoIter<(&'de CStr, BtValueConst)>,
+    /// The value of the last key
+    value: Option<(&'de str, BtValueConst)>,
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        match self.entries.next() {
+            Some((key, value)) => {
+                let key = to_str(key)?;
+                self.value = Some((key, value));
+                seed.deserialize(BorrowedStrDeserializer::<Error>::new(key))
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let (key, value) = self
+            .value
+            .take()
+            .expect("next_value_seed called before next_key_seed");
+        seed.deserialize(ValueDeserializer::new(value.as_ptr()))
+            .map_err(|error| error.in_key(key))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+struct VariantAccess<'de> {
+    variant: &'de str,
+    value: BtValueConst,
+    phantom: PhantomData<&'de BtValueConst>,
+}
+
+impl<'de> de::EnumAccess<'de> for VariantAccess<'de> {
+    type Error = Error;
+    type Variant = ValueDeserializer<'de>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self::Variant)> {
+        let variant = seed.deserialize(BorrowedStrDeserializer::<Error>::new(self.variant))?;
+        Ok((variant, ValueDeserializer::new(self.value.as_ptr())))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for ValueDeserializer<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        <()>::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
+
+/// Serialize `value` into a new value
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<BtValueOwned> {
+    value.serialize(ValueSerializer)
+}
+
+/// Fails if the value could not be created
+fn created(value: BtValueOwned) -> Result<BtValueOwned> {
+    if value.is_empty() {
+        Err(crate::error::Error::new(crate::error::ErrorKind::MemoryError, "BtValue").into())
+    } else {
+        Ok(value)
+    }
+}
+
+fn null() -> BtValueOwned {
+    BtValueOwned::new_ref(&BtValue::from_ptr(unsafe { bt_value_null }))
+}
+
+/// A map with the name of the variant as only key
+fn variant_map(variant: &'static str, value: BtValueOwned) -> Result<BtValueOwned> {
+    let mut map = created(BtValue::map_create())?;
+    map.try_map_insert_entry(variant, &value)?;
+    Ok(map)
+}
+
+struct ValueSerializer;
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = BtValueOwned;
+    type Error = Error;
+    type SerializeSeq = ArraySerializer;
+    type SerializeTuple = ArraySerializer;
+    type SerializeTupleStruct = ArraySerializer;
+    type SerializeTupleVariant = ArraySerializer;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = MapSerializer;
+    type SerializeStructVariant = MapSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<BtValueOwned> {
+        created(BtValue::bool_create_init(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<BtValueOwned> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<BtValueOwned> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<BtValueOwned> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<BtValueOwned> {
+        created(BtValue::integer_signed_create_init(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<BtValueOwned> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<BtValueOwned> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<BtValueOwned> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<BtValueOwned> {
+        created(BtValue::integer_unsigned_create_init(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<BtValueOwned> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<BtValueOwned> {
+        created(BtValue::real_create_init(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<BtValueOwned> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<BtValueOwned> {
+        let value = BtValue::string_create_init(v)
+            .map_err(|_| Error::new(format!("NUL character in string {v:?}")))?;
+        created(value)
+    }
+
+    /// Bytes are an array of unsigned integers
+    fn serialize_bytes(self, v: &[u8]) -> Result<BtValueOwned> {
+        let mut array = created(BtValue::array_create())?;
+        for byte in v {
+            array.try_array_append_unsigned_integer_element((*byte).into())?;
+        }
+        Ok(array)
+    }
+
+    fn serialize_none(self) -> Result<BtValueOwned> {
+        Ok(null())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<BtValueOwned> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<BtValueOwned> {
+        Ok(null())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<BtValueOwned> {
+        Ok(null())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<BtValueOwned> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<BtValueOwned> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<BtValueOwned> {
+        variant_map(variant, to_value(value)?)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<ArraySerializer> {
+        Ok(ArraySerializer {
+            array: created(BtValue::array_create())?,
+            variant: None,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<ArraySerializer> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<ArraySerializer> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<ArraySerializer> {
+        Ok(ArraySerializer {
+            array: created(BtValue::array_create())?,
+            variant: Some(variant),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapSerializer> {
+        Ok(MapSerializer {
+            map: created(BtValue::map_create())?,
+            key: None,
+            variant: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<MapSerializer> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<MapSerializer> {
+        Ok(MapSerializer {
+            map: created(BtValue::map_create())?,
+            key: None,
+            variant: Some(variant),
+        })
+    }
+}
+
+struct ArraySerializer {
+    array: BtValueOwned,
+    /// The name of the variant of a tuple variant
+    variant: Option<&'static str>,
+}
+
+impl ArraySerializer {
+    fn append<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let index = self.array.array_get_length();
+        let element = to_value(value).map_err(|error| error.in_key(&index.to_string()))?;
+        Ok(self.array.try_array_append_element(&element)?)
+    }
+
+    fn end(self) -> Result<BtValueOwned> {
+        match self.variant {
+            Some(variant) => variant_map(variant, self.array),
+            None => Ok(self.array),
+        }
+    }
+}
+
+impl ser::SerializeSeq for ArraySerializer {
+    type Ok = BtValueOwned;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.append(value)
+    }
+
+    fn end(self) -> Result<BtValueOwned> {
+        ArraySerializer::end(self)
+    }
+}
+
+impl ser::SerializeTuple for ArraySerializer {
+    type Ok = BtValueOwned;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.append(value)
+    }
+
+    fn end(self) -> Result<BtValueOwned> {
+        ArraySerializer::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for ArraySerializer {
+    type Ok = BtValueOwned;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.append(value)
+    }
+
+    fn end(self) -> Result<BtValueOwned> {
+        ArraySerializer::end(self)
+    }
+}
+
+impl ser::SerializeTupleVariant for ArraySerializer {
+    type Ok = BtValueOwned;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.append(value)
+    }
+
+    fn end(self) -> Result<BtValueOwned> {
+        ArraySerializer::end(self)
+    }
+}
+
+struct MapSerializer {
+    map: BtValueOwned,
+    /// The key of the next value
+    key: Option<String>,
+    /// The name of the variant of a struct variant
+    variant: Option<&'static str>,
+}
+
+impl MapSerializer {
+    fn insert<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<()> {
+        let entry = to_value(value).map_err(|error| error.in_key(key))?;
+        Ok(self.map.try_map_insert_entry(key, &entry)?)
+    }
+
+    fn end(self) -> Result<BtValueOwned> {
+        match self.variant {
+            Some(variant) => variant_map(variant, self.map),
+            None => Ok(self.map),
+        }
+    }
+}
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = BtValueOwned;
+    type Error = Error;
+
+    /// Keys are strings, integers are converted to strings
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        let key = to_value(key)?;
+        self.key = Some(match key.get_type() {
+            BtValueType::String => to_str(key.string_get())?.to_string(),
+            BtValueType::UnsignedInteger => key.integer_unsigned_get().to_string(),
+            BtValueType::SignedInteger => key.integer_signed_get().to_string(),
+            _ => return Err(Error::new("map keys must be strings or integers")),
+        });
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let key = self
+            .key
+            .take()
+            .expect("serialize_value called before serialize_key");
+        self.insert(&key, value)
+    }
+
+    fn end(self) -> Result<BtValueOwned> {
+        MapSerializer::end(self)
+    }
+}
+
+impl ser::SerializeStruct for MapSerializer {
+    type Ok = BtValueOwned;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<BtValueOwned> {
+        MapSerializer::end(self)
+    }
+}
+
+impl ser::SerializeStructVariant for MapSerializer {
+    type Ok = BtValueOwned;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<BtValueOwned> {
+        MapSerializer::end(self)
+    }
+}
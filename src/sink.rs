@@ -0,0 +1,289 @@
+// Copyright 2023 - 2023, Karsten Becker and the rust babeltrace2-plugin contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+//! Trait based sink component classes
+//!
+//! Implement [`SinkComponent`] and either create the component class at runtime with
+//! [`create_component_class`], or reference the trampolines of this module (for example
+//! `sink::consume::<MySink>`) in [`bt_plugin!`](crate::bt_plugin). The trampolines share the
+//! private data of the component, so `initialize`, `finalize`, `graph_is_configured` and
+//! `consume` always have to be used together. They are only meant to be called by
+//! libbabeltrace2.
+#![allow(clippy::missing_safety_doc)]
+use std::ffi::{c_void, CStr};
+use std::os::raw::c_char;
+
+use crate::bt2::binding::*;
+use crate::bt2::*;
+use crate::component;
+use crate::error::{Error, ErrorKind};
+use crate::{borrow_boxed_data, get_iterator};
+use crate::params::{set_help, ParamSchema};
+use crate::upstream::{MessagePoll, UpstreamIterator};
+
+/// The methods of a sink component class. The implementing type is the private data of
+/// each component instance, it is created by [`SinkComponent::initialize`] and dropped
+/// after [`SinkComponent::finalize`].
+///
+/// Once the graph is configured, a message iterator is created for each connected input
+/// port. Every call of the consume method then passes the next batch of messages of one of
+/// these iterators, in turns, to [`SinkComponent::consume`]. Iterators without messages for
+/// now are skipped, the consume method only returns
+/// [`BtComponentClassSinkConsumeMethodStatus::Again`] if none of them has messages. It
+/// returns [`BtComponentClassSinkConsumeMethodStatus::End`] once all iterators have ended.
+pub trait SinkComponent: Sized {
+    /// The initialization parameters, which are validated before [`initialize`](Self::initialize)
+    /// if given. See [`params`](crate::params).
+    const PARAMS: Option<ParamSchema> = None;
+
+    /// Called when a component is instantiated. This typically adds the input ports.
+    fn initialize(
+        self_component: &mut BtSelfComponentSink,
+        configuration: &mut BtSelfComponentSinkConfiguration,
+        params: &BtValueConst,
+    ) -> Result<Self, BtComponentClassInitializeMethodStatus>;
+
+    /// Called before the component is destroyed.
+    fn finalize(&mut self, _self_component: &mut BtSelfComponentSink) {}
+
+    /// Called once the graph is configured, after the upstream message iterators are created.
+    fn graph_is_configured(
+        &mut self,
+        _self_component: &mut BtSelfComponentSink,
+    ) -> BtComponentClassSinkGraphIsConfiguredMethodStatus {
+        BtComponentClassSinkGraphIsConfiguredMethodStatus::Ok
+    }
+
+    /// Called with a batch of messages of one upstream message iterator. The references of
+    /// the messages are released after this returns, use [`BtMessageConstOwned::new_ref`] to
+    /// keep a message.
+    fn consume(
+        &mut self,
+        self_component: &mut BtSelfComponentSink,
+        messages: &[BtMessageConstOwned],
+    ) -> BtComponentClassSinkConsumeMethodStatus;
+
+    /// Called when one of the input ports is connected to an output port.
+    fn input_port_connected(
+        &mut self,
+        _self_component: &mut BtSelfComponentSink,
+        _self_port: &mut BtSelfComponentPortInput,
+        _other_port: &BtPortOutputConst,
+    ) -> BtComponentClassPortConnectedMethodStatus {
+        BtComponentClassPortConnectedMethodStatus::Ok
+    }
+
+    /// Called to query the object `object_name`. The reference of the returned value is
+    /// passed to the caller.
+    fn query(
+        _self_component_class: &mut BtSelfComponentClassSink,
+        _query_executor: &mut BtPrivateQueryExecutor,
+        _object_name: &CStr,
+        _params: &BtValueConst,
+    ) -> Result<BtValueConstOwned, BtComponentClassQueryMethodStatus> {
+        Err(BtComponentClassQueryMethodStatus::UnknownObject)
+    }
+
+    /// Called to get the supported message interchange protocol versions. Only version 0 is
+    /// supported by default, just like without this method.
+    fn get_supported_mip_versions(
+        _self_component_class: &mut BtSelfComponentClassSink,
+        _params: &BtValueConst,
+        _logging_level: BtLoggingLevel,
+        supported_versions: &mut BtIntegerRangeSetUnsigned,
+    ) -> BtComponentClassGetSupportedMipVersionsMethodStatus {
+        component::mip_version_0(supported_versions)
+    }
+}
+
+/// The private data of a sink component
+struct SinkData<T> {
+    component: T,
+    iterators: Vec<UpstreamIterator>,
+    next_iterator: usize,
+}
+
+/// Creates a sink component class named `name` with the methods of `T`. Fails if `name`
+/// contains a NUL character.
+pub fn create_component_class<T: SinkComponent>(
+    name: &str,
+) -> crate::error::Result<BtComponentClassSinkOwned> {
+    let mut component_class = BtComponentClassSink::create(name, Some(consume::<T>))?;
+    if component_class.is_empty() {
+        return Err(Error::new(ErrorKind::MemoryError, "BtComponentClassSink"));
+    }
+    component_class.set_initialize_method(Some(initialize::<T>));
+    component_class.set_finalize_method(Some(finalize::<T>));
+    component_class.set_graph_is_configured_method(Some(graph_is_configured::<T>));
+    component_class.set_input_port_connected_method(Some(input_port_connected::<T>));
+    component_class.set_query_method(Some(query::<T>));
+    component_class.set_get_supported_mip_versions_method(Some(get_supported_mip_versions::<T>));
+    if let Some(schema) = T::PARAMS {
+        set_help(&mut component_class.as_component_class_inline(), &schema)?;
+    }
+    Ok(component_class)
+}
+
+/// Trampoline for [`SinkComponent::initialize`]
+pub unsafe extern "C" fn initialize<T: SinkComponent>(
+    self_component: *mut bt_self_component_sink,
+    configuration: *mut bt_self_component_sink_configuration,
+    params: *const bt_value,
+    _initialize_method_data: *mut c_void,
+) -> bt_component_class_initialize_method_status {
+    let mut self_component = BtSelfComponentSink::from_ptr(self_component);
+    let mut configuration = BtSelfComponentSinkConfiguration::from_ptr(configuration);
+    component::initialize(
+        &mut self_component,
+        T::PARAMS,
+        params,
+        |self_component, params| T::initialize(self_component, &mut configuration, params),
+        |component| SinkData {
+            component,
+            iterators: Vec::new(),
+            next_iterator: 0,
+        },
+    )
+}
+
+/// Trampoline for [`SinkComponent::finalize`]
+pub unsafe extern "C" fn finalize<T: SinkComponent>(
+    self_component: *mut bt_self_component_sink,
+) {
+    let mut self_component = BtSelfComponentSink::from_ptr(self_component);
+    component::finalize(&mut self_component, |data: &mut SinkData<T>, self_component| {
+        data.component.finalize(self_component)
+    });
+}
+
+/// Trampoline for [`SinkComponent::graph_is_configured`], creates the upstream message
+/// iterators first.
+pub unsafe extern "C" fn graph_is_configured<T: SinkComponent>(
+    self_component: *mut bt_self_component_sink,
+) -> bt_component_class_sink_graph_is_configured_method_status {
+    // The ports are borrowed from a second handle, the iterators are created with the first
+    let mut ports = BtSelfComponentSink::from_ptr(self_component);
+    let mut self_component = BtSelfComponentSink::from_ptr(self_component);
+    let data: &mut SinkData<T> = borrow_boxed_data(&mut self_component);
+    let port_count = ports.as_component_sink_inline().get_input_port_count();
+    for index in 0..port_count {
+        let in_port = ports.borrow_input_port_by_index(index);
+        if !in_port
+            .as_port_input_inline()
+            .as_port_const_inline()
+            .is_connected()
+        {
+            continue;
+        }
+        match get_iterator(&self_component, &in_port) {
+            Ok(iterator) => data.iterators.push(UpstreamIterator::new(iterator)),
+            Err(status) => {
+                return BtComponentClassSinkGraphIsConfiguredMethodStatus::from(status).into()
+            }
+        }
+    }
+    component::catch_panic(BtComponentClassSinkGraphIsConfiguredMethodStatus::Error, || {
+        data.component.graph_is_configured(&mut self_component)
+    })
+    .into()
+}
+
+/// Trampoline for [`SinkComponent::consume`]
+pub unsafe extern "C" fn consume<T: SinkComponent>(
+    self_component: *mut bt_self_component_sink,
+) -> bt_component_class_sink_consume_method_status {
+    let mut self_component = BtSelfComponentSink::from_ptr(self_component);
+    let data: &mut SinkData<T> = borrow_boxed_data(&mut self_component);
+    if data.iterators.is_empty() {
+        return BtComponentClassSinkConsumeMethodStatus::End.into();
+    }
+    // Try the iterators in turns, skipping the ones without messages for now
+    let count = data.iterators.len();
+    for offset in 0..count {
+        let index = (data.next_iterator + offset) % count;
+        let iterator = &mut data.iterators[index];
+        return match iterator.next_batch() {
+            Ok(MessagePoll::Ready(messages)) => {
+                data.next_iterator = index + 1;
+                let status =
+                    component::catch_panic(BtComponentClassSinkConsumeMethodStatus::Error, || {
+                        data.component.consume(&mut self_component, messages)
+                    });
+                iterator.release_returned_batch();
+                status.into()
+            }
+            Ok(MessagePoll::Again) => continue,
+            Ok(MessagePoll::End) => {
+                data.iterators.remove(index);
+                data.next_iterator = index;
+                if data.iterators.is_empty() {
+                    BtComponentClassSinkConsumeMethodStatus::End.into()
+                } else {
+                    BtComponentClassSinkConsumeMethodStatus::Ok.into()
+                }
+            }
+            Err(error) if error.kind() == ErrorKind::MemoryError => {
+                BtComponentClassSinkConsumeMethodStatus::MemoryError.into()
+            }
+            Err(_) => BtComponentClassSinkConsumeMethodStatus::Error.into(),
+        };
+    }
+    BtComponentClassSinkConsumeMethodStatus::Again.into()
+}
+
+/// Trampoline for [`SinkComponent::input_port_connected`]
+pub unsafe extern "C" fn input_port_connected<T: SinkComponent>(
+    self_component: *mut bt_self_component_sink,
+    self_port: *mut bt_self_component_port_input,
+    other_port: *const bt_port_output,
+) -> bt_component_class_port_connected_method_status {
+    let mut self_component = BtSelfComponentSink::from_ptr(self_component);
+    let mut self_port = BtSelfComponentPortInput::from_ptr(self_port);
+    let other_port = BtPortOutputConst::from_ptr(other_port);
+    component::with_data(
+        &mut self_component,
+        BtComponentClassPortConnectedMethodStatus::Error,
+        |data: &mut SinkData<T>, self_component| {
+            data.component
+                .input_port_connected(self_component, &mut self_port, &other_port)
+        },
+    )
+    .into()
+}
+
+/// Trampoline for [`SinkComponent::query`]
+pub unsafe extern "C" fn query<T: SinkComponent>(
+    self_component_class: *mut bt_self_component_class_sink,
+    query_executor: *mut bt_private_query_executor,
+    object_name: *const c_char,
+    params: *const bt_value,
+    _method_data: *mut c_void,
+    result: *mut *const bt_value,
+) -> bt_component_class_query_method_status {
+    let mut self_component_class = BtSelfComponentClassSink::from_ptr(self_component_class);
+    component::query(
+        &mut self_component_class,
+        query_executor,
+        object_name,
+        params,
+        result,
+        T::query,
+    )
+}
+
+/// Trampoline for [`SinkComponent::get_supported_mip_versions`]
+pub unsafe extern "C" fn get_supported_mip_versions<T: SinkComponent>(
+    self_component_class: *mut bt_self_component_class_sink,
+    params: *const bt_value,
+    _method_data: *mut c_void,
+    logging_level: bt_logging_level,
+    supported_versions: *mut bt_integer_range_set_unsigned,
+) -> bt_component_class_get_supported_mip_versions_method_status {
+    let mut self_component_class = BtSelfComponentClassSink::from_ptr(self_component_class);
+    component::get_supported_mip_versions(
+        &mut self_component_class,
+        params,
+        logging_level,
+        supported_versions,
+        T::get_supported_mip_versions,
+    )
+}
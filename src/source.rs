@@ -0,0 +1,172 @@
+// Copyright 2023 - 2023, Karsten Becker and the rust babeltrace2-plugin contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+//! Trait based source component classes
+//!
+//! Implement [`SourceComponent`] and either create the component class at runtime with
+//! [`create_component_class`], or reference the trampolines of this module (for example
+//! `source::initialize::<MySource>`) in [`bt_plugin!`](crate::bt_plugin). The trampolines
+//! are only meant to be called by libbabeltrace2.
+#![allow(clippy::missing_safety_doc)]
+use std::ffi::{c_void, CStr};
+use std::os::raw::c_char;
+
+use crate::bt2::binding::*;
+use crate::bt2::*;
+use crate::component;
+use crate::error::{Error, ErrorKind};
+use crate::params::{set_help, ParamSchema};
+
+/// The methods of a source component class. The implementing type is the private data of
+/// each component instance, it is created by [`SourceComponent::initialize`] and dropped
+/// after [`SourceComponent::finalize`].
+pub trait SourceComponent: Sized {
+    /// The initialization parameters, which are validated before [`initialize`](Self::initialize)
+    /// if given. See [`params`](crate::params).
+    const PARAMS: Option<ParamSchema> = None;
+
+    /// Called when a component is instantiated. This typically adds the output ports.
+    fn initialize(
+        self_component: &mut BtSelfComponentSource,
+        configuration: &mut BtSelfComponentSourceConfiguration,
+        params: &BtValueConst,
+    ) -> Result<Self, BtComponentClassInitializeMethodStatus>;
+
+    /// Called before the component is destroyed.
+    fn finalize(&mut self, _self_component: &mut BtSelfComponentSource) {}
+
+    /// Called when one of the output ports is connected to an input port.
+    fn output_port_connected(
+        &mut self,
+        _self_component: &mut BtSelfComponentSource,
+        _self_port: &mut BtSelfComponentPortOutput,
+        _other_port: &BtPortInputConst,
+    ) -> BtComponentClassPortConnectedMethodStatus {
+        BtComponentClassPortConnectedMethodStatus::Ok
+    }
+
+    /// Called to query the object `object_name`. The reference of the returned value is
+    /// passed to the caller.
+    fn query(
+        _self_component_class: &mut BtSelfComponentClassSource,
+        _query_executor: &mut BtPrivateQueryExecutor,
+        _object_name: &CStr,
+        _params: &BtValueConst,
+    ) -> Result<BtValueConstOwned, BtComponentClassQueryMethodStatus> {
+        Err(BtComponentClassQueryMethodStatus::UnknownObject)
+    }
+
+    /// Called to get the supported message interchange protocol versions. Only version 0 is
+    /// supported by default, just like without this method.
+    fn get_supported_mip_versions(
+        _self_component_class: &mut BtSelfComponentClassSource,
+        _params: &BtValueConst,
+        _logging_level: BtLoggingLevel,
+        supported_versions: &mut BtIntegerRangeSetUnsigned,
+    ) -> BtComponentClassGetSupportedMipVersionsMethodStatus {
+        component::mip_version_0(supported_versions)
+    }
+}
+
+/// Creates a source component class named `name` with the methods of `T` and the message
+/// iterator class `message_iterator_class`. Fails if `name` contains a NUL character.
+pub fn create_component_class<T: SourceComponent>(
+    name: &str,
+    message_iterator_class: &BtMessageIteratorClass,
+) -> crate::error::Result<BtComponentClassSourceOwned> {
+    let mut component_class = BtComponentClassSource::create(name, message_iterator_class)?;
+    if component_class.is_empty() {
+        return Err(Error::new(ErrorKind::MemoryError, "BtComponentClassSource"));
+    }
+    component_class.set_initialize_method(Some(initialize::<T>));
+    component_class.set_finalize_method(Some(finalize::<T>));
+    component_class.set_output_port_connected_method(Some(output_port_connected::<T>));
+    component_class.set_query_method(Some(query::<T>));
+    component_class.set_get_supported_mip_versions_method(Some(get_supported_mip_versions::<T>));
+    if let Some(schema) = T::PARAMS {
+        set_help(&mut component_class.as_component_class_inline(), &schema)?;
+    }
+    Ok(component_class)
+}
+
+/// Trampoline for [`SourceComponent::initialize`]
+pub unsafe extern "C" fn initialize<T: SourceComponent>(
+    self_component: *mut bt_self_component_source,
+    configuration: *mut bt_self_component_source_configuration,
+    params: *const bt_value,
+    _initialize_method_data: *mut c_void,
+) -> bt_component_class_initialize_method_status {
+    let mut self_component = BtSelfComponentSource::from_ptr(self_component);
+    let mut configuration = BtSelfComponentSourceConfiguration::from_ptr(configuration);
+    component::initialize(
+        &mut self_component,
+        T::PARAMS,
+        params,
+        |self_component, params| T::initialize(self_component, &mut configuration, params),
+        |data| data,
+    )
+}
+
+/// Trampoline for [`SourceComponent::finalize`]
+pub unsafe extern "C" fn finalize<T: SourceComponent>(
+    self_component: *mut bt_self_component_source,
+) {
+    let mut self_component = BtSelfComponentSource::from_ptr(self_component);
+    component::finalize(&mut self_component, T::finalize);
+}
+
+/// Trampoline for [`SourceComponent::output_port_connected`]
+pub unsafe extern "C" fn output_port_connected<T: SourceComponent>(
+    self_component: *mut bt_self_component_source,
+    self_port: *mut bt_self_component_port_output,
+    other_port: *const bt_port_input,
+) -> bt_component_class_port_connected_method_status {
+    let mut self_component = BtSelfComponentSource::from_ptr(self_component);
+    let mut self_port = BtSelfComponentPortOutput::from_ptr(self_port);
+    let other_port = BtPortInputConst::from_ptr(other_port);
+    component::with_data(
+        &mut self_component,
+        BtComponentClassPortConnectedMethodStatus::Error,
+        |data: &mut T, self_component| {
+            data.output_port_connected(self_component, &mut self_port, &other_port)
+        },
+    )
+    .into()
+}
+
+/// Trampoline for [`SourceComponent::query`]
+pub unsafe extern "C" fn query<T: SourceComponent>(
+    self_component_class: *mut bt_self_component_class_source,
+    query_executor: *mut bt_private_query_executor,
+    object_name: *const c_char,
+    params: *const bt_value,
+    _method_data: *mut c_void,
+    result: *mut *const bt_value,
+) -> bt_component_class_query_method_status {
+    let mut self_component_class = BtSelfComponentClassSource::from_ptr(self_component_class);
+    component::query(
+        &mut self_component_class,
+        query_executor,
+        object_name,
+        params,
+        result,
+        T::query,
+    )
+}
+
+/// Trampoline for [`SourceComponent::get_supported_mip_versions`]
+pub unsafe extern "C" fn get_supported_mip_versions<T: SourceComponent>(
+    self_component_class: *mut bt_self_component_class_source,
+    params: *const bt_value,
+    _method_data: *mut c_void,
+    logging_level: bt_logging_level,
+    supported_versions: *mut bt_integer_range_set_unsigned,
+) -> bt_component_class_get_supported_mip_versions_method_status {
+    let mut self_component_class = BtSelfComponentClassSource::from_ptr(self_component_class);
+    component::get_supported_mip_versions(
+        &mut self_component_class,
+        params,
+        logging_level,
+        supported_versions,
+        T::get_supported_mip_versions,
+    )
+}
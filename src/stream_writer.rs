@@ -0,0 +1,395 @@
+// Copyright 2023 - 2023, Karsten Becker and the rust babeltrace2-plugin contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+//! The messages of a stream, in the order libbabeltrace2 requires
+//!
+//! A [`StreamWriter`] creates the stream beginning, packet beginning, packet end and stream
+//! end messages when they are due, and rejects clock snapshots going back in time. The
+//! messages are queued until [`StreamWriter::flush`] moves them to the [`MessageBuffer`] of
+//! the `next` method. A method which fails queues no message, apart from allocation
+//! failures:
+//!
+//! ```ignore
+//! let mut event = self.writer.event(self_message_iterator, &event_class, Some(timestamp))?;
+//! sample.write_to(&mut event.borrow_payload_field())?;
+//! self.writer.flush(messages);
+//! ```
+use std::collections::VecDeque;
+
+use crate::bt2::*;
+use crate::error::{Error, ErrorKind, Result};
+use crate::message::MessageBuilder;
+use crate::message_iterator::MessageBuffer;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Created,
+    Began,
+    Ended,
+}
+
+/// Writes the messages of a stream
+pub struct StreamWriter {
+    stream: BtStreamOwned,
+    state: State,
+    packet: Option<BtPacketOwned>,
+    last_clock_snapshot: Option<u64>,
+    pending: VecDeque<BtMessageOwned>,
+}
+
+/// The method of the writer whose messages are checked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operation {
+    Begin,
+    BeginPacket,
+    EndPacket,
+    Event,
+    DiscardedEvents {
+        clock_snapshots: Option<(u64, u64)>,
+        count: Option<u64>,
+    },
+    End,
+}
+
+fn writer_error(kind: ErrorKind) -> Error {
+    Error::new(kind, "StreamWriter")
+}
+
+impl StreamWriter {
+    pub fn new(stream: BtStreamOwned) -> StreamWriter {
+        StreamWriter {
+            stream,
+            state: State::Created,
+            packet: None,
+            last_clock_snapshot: None,
+            pending: VecDeque::new(),
+        }
+    }
+
+    pub fn stream(&self) -> &BtStream {
+        &self.stream
+    }
+
+    /// The open packet, if there is one
+    pub fn packet(&self) -> Option<&BtPacket> {
+        self.packet.as_deref()
+    }
+
+    /// The stream end message was created
+    pub fn is_ended(&self) -> bool {
+        self.state == State::Ended
+    }
+
+    /// There are messages which were not flushed yet
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Move the queued messages to `messages` until it is full
+    pub fn flush(&mut self, messages: &mut MessageBuffer) {
+        while !messages.is_full() {
+            match self.pending.pop_front() {
+                // Cannot fail, the buffer is not full
+                Some(message) => drop(messages.push(message.into_const())),
+                None => break,
+            }
+        }
+    }
+
+    fn stream_const(&self) -> BtStreamConst {
+        BtStreamConst::from_ptr(self.stream.as_ptr())
+    }
+
+    fn has_default_clock_class(&self) -> bool {
+        !self
+            .stream
+            .borrow_class_const()
+            .borrow_default_clock_class_const()
+            .as_ptr()
+            .is_null()
+    }
+
+    fn check_not_ended(&self) -> Result<()> {
+        if self.state == State::Ended {
+            Err(writer_error(ErrorKind::End))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Fails if the messages of `operation` can't be created: if the stream ended, if
+    /// `clock_snapshot` is before the last one, or if a clock snapshot is given without default
+    /// clock class or is missing for one of the messages. Runs before anything is queued.
+    fn check_clock_snapshot(
+        &self,
+        operation: Operation,
+        clock_snapshot: Option<u64>,
+    ) -> Result<()> {
+        self.check_not_ended()?;
+        let stream_class = self.stream.borrow_class_const();
+        if clock_snapshot.is_some() && !self.has_default_clock_class() {
+            return Err(writer_error(ErrorKind::ClockSnapshotNotSupported));
+        }
+        if matches!(
+            (clock_snapshot, self.last_clock_snapshot),
+            (Some(value), Some(last)) if value < last
+        ) {
+            return Err(writer_error(ErrorKind::ClockSnapshotOutOfOrder));
+        }
+        let closes_packet = self.packet.is_some()
+            && matches!(
+                operation,
+                Operation::BeginPacket | Operation::EndPacket | Operation::End
+            );
+        let opens_packet = match operation {
+            Operation::BeginPacket => true,
+            Operation::Event => self.packet.is_none() && stream_class.supports_packets(),
+            _ => false,
+        };
+        if opens_packet && !stream_class.supports_packets() {
+            return Err(writer_error(ErrorKind::PacketNotSupported));
+        }
+        // The packet messages fall back to the last clock snapshot
+        let packet_clock_snapshot_required = (closes_packet
+            && stream_class.packets_have_end_default_clock_snapshot())
+            || (opens_packet && stream_class.packets_have_beginning_default_clock_snapshot());
+        if packet_clock_snapshot_required && clock_snapshot.or(self.last_clock_snapshot).is_none() {
+            return Err(writer_error(ErrorKind::ClockSnapshotRequired));
+        }
+        match operation {
+            Operation::Event if clock_snapshot.is_none() && self.has_default_clock_class() => {
+                Err(writer_error(ErrorKind::ClockSnapshotRequired))
+            }
+            Operation::DiscardedEvents {
+                clock_snapshots,
+                count,
+            } => {
+                if !stream_class.supports_discarded_events() {
+                    return Err(writer_error(ErrorKind::DiscardedNotSupported));
+                }
+                match (
+                    stream_class.discarded_events_have_default_clock_snapshots(),
+                    clock_snapshots,
+                ) {
+                    (true, None) => Err(writer_error(ErrorKind::ClockSnapshotRequired)),
+                    (false, Some(_)) => Err(writer_error(ErrorKind::ClockSnapshotNotSupported)),
+                    (_, Some((beginning, end))) if end < beginning => {
+                        Err(writer_error(ErrorKind::ClockSnapshotOutOfOrder))
+                    }
+                    _ if count == Some(0) => Err(writer_error(ErrorKind::UserError)),
+                    _ => Ok(()),
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn advance(&mut self, clock_snapshot: Option<u64>) {
+        if clock_snapshot.is_some() {
+            self.last_clock_snapshot = clock_snapshot;
+        }
+    }
+
+    /// The given clock snapshot, or the last one, if the message has one
+    fn clock_snapshot_for(
+        &self,
+        has_clock_snapshot: bool,
+        clock_snapshot: Option<u64>,
+    ) -> Option<u64> {
+        if has_clock_snapshot {
+            clock_snapshot.or(self.last_clock_snapshot)
+        } else {
+            None
+        }
+    }
+
+    fn ensure_began(
+        &mut self,
+        self_message_iterator: &BtSelfMessageIterator,
+        clock_snapshot: Option<u64>,
+    ) -> Result<()> {
+        if self.state == State::Created {
+            let message = MessageBuilder::new(self_message_iterator)
+                .stream_beginning(&self.stream_const(), clock_snapshot)?;
+            self.pending.push_back(message);
+            self.state = State::Began;
+        }
+        Ok(())
+    }
+
+    /// Create the stream beginning message, if it was not created yet. Otherwise nothing is
+    /// queued and `clock_snapshot` doesn't become the last clock snapshot.
+    pub fn begin(
+        &mut self,
+        self_message_iterator: &BtSelfMessageIterator,
+        clock_snapshot: Option<u64>,
+    ) -> Result<()> {
+        self.check_clock_snapshot(Operation::Begin, clock_snapshot)?;
+        if self.state == State::Created {
+            self.ensure_began(self_message_iterator, clock_snapshot)?;
+            self.advance(clock_snapshot);
+        }
+        Ok(())
+    }
+
+    /// A new packet of the stream, with the context fields set by `init`
+    fn create_packet(
+        &self,
+        init: impl FnOnce(&mut BtPacketOwned) -> Result<()>,
+    ) -> Result<BtPacketOwned> {
+        let mut packet = BtPacket::create(&self.stream_const());
+        if packet.as_ptr().is_null() {
+            return Err(writer_error(ErrorKind::MemoryError));
+        }
+        init(&mut packet)?;
+        Ok(packet)
+    }
+
+    fn open_packet(
+        &mut self,
+        self_message_iterator: &BtSelfMessageIterator,
+        clock_snapshot: Option<u64>,
+        packet: BtPacketOwned,
+    ) -> Result<()> {
+        let has_clock_snapshot = self
+            .stream
+            .borrow_class_const()
+            .packets_have_beginning_default_clock_snapshot();
+        let message = MessageBuilder::new(self_message_iterator).packet_beginning(
+            &BtPacketConst::from_ptr(packet.as_ptr()),
+            self.clock_snapshot_for(has_clock_snapshot, clock_snapshot),
+        )?;
+        self.pending.push_back(message);
+        self.packet = Some(packet);
+        Ok(())
+    }
+
+    fn close_packet(
+        &mut self,
+        self_message_iterator: &BtSelfMessageIterator,
+        clock_snapshot: Option<u64>,
+    ) -> Result<()> {
+        if let Some(packet) = &self.packet {
+            let has_clock_snapshot = self
+                .stream
+                .borrow_class_const()
+                .packets_have_end_default_clock_snapshot();
+            let message = MessageBuilder::new(self_message_iterator).packet_end(
+                &BtPacketConst::from_ptr(packet.as_ptr()),
+                self.clock_snapshot_for(has_clock_snapshot, clock_snapshot),
+            )?;
+            self.pending.push_back(message);
+            self.packet = None;
+        }
+        Ok(())
+    }
+
+    /// Begin a new packet, after ending the open one. `init` sets the context fields of the
+    /// packet before any message is created.
+    pub fn begin_packet(
+        &mut self,
+        self_message_iterator: &BtSelfMessageIterator,
+        clock_snapshot: Option<u64>,
+        init: impl FnOnce(&mut BtPacketOwned) -> Result<()>,
+    ) -> Result<()> {
+        self.check_clock_snapshot(Operation::BeginPacket, clock_snapshot)?;
+        let packet = self.create_packet(init)?;
+        self.ensure_began(self_message_iterator, clock_snapshot)?;
+        self.close_packet(self_message_iterator, clock_snapshot)?;
+        self.open_packet(self_message_iterator, clock_snapshot, packet)?;
+        self.advance(clock_snapshot);
+        Ok(())
+    }
+
+    /// End the open packet, if there is one
+    pub fn end_packet(
+        &mut self,
+        self_message_iterator: &BtSelfMessageIterator,
+        clock_snapshot: Option<u64>,
+    ) -> Result<()> {
+        self.check_clock_snapshot(Operation::EndPacket, clock_snapshot)?;
+        self.close_packet(self_message_iterator, clock_snapshot)?;
+        self.advance(clock_snapshot);
+        Ok(())
+    }
+
+    /// Create an event message of `event_class`, after beginning the stream and a packet if
+    /// needed. The returned event is the one of the queued message, to fill in its fields.
+    pub fn event(
+        &mut self,
+        self_message_iterator: &BtSelfMessageIterator,
+        event_class: &BtEventClassConst,
+        clock_snapshot: Option<u64>,
+    ) -> Result<BtEventRef<'_>> {
+        self.check_clock_snapshot(Operation::Event, clock_snapshot)?;
+        let packet = if self.packet.is_none() && self.stream.borrow_class_const().supports_packets()
+        {
+            Some(self.create_packet(|_| Ok(()))?)
+        } else {
+            None
+        };
+        self.ensure_began(self_message_iterator, clock_snapshot)?;
+        if let Some(packet) = packet {
+            self.open_packet(self_message_iterator, clock_snapshot, packet)?;
+        }
+        let packet = self
+            .packet
+            .as_ref()
+            .map(|packet| BtPacketConst::from_ptr(packet.as_ptr()));
+        let message = MessageBuilder::new(self_message_iterator).event(
+            event_class,
+            &self.stream_const(),
+            packet.as_ref(),
+            clock_snapshot,
+        )?;
+        self.advance(clock_snapshot);
+        self.pending.push_back(message);
+        let message = self
+            .pending
+            .back_mut()
+            .expect("The event message was just queued");
+        Ok(message.event_borrow_event())
+    }
+
+    /// Create a discarded events message for the time range `clock_snapshots`
+    pub fn discarded_events(
+        &mut self,
+        self_message_iterator: &BtSelfMessageIterator,
+        clock_snapshots: Option<(u64, u64)>,
+        count: Option<u64>,
+    ) -> Result<()> {
+        let beginning = clock_snapshots.map(|(beginning, _)| beginning);
+        let end = clock_snapshots.map(|(_, end)| end);
+        let operation = Operation::DiscardedEvents {
+            clock_snapshots,
+            count,
+        };
+        self.check_clock_snapshot(operation, beginning)?;
+        self.ensure_began(self_message_iterator, beginning)?;
+        let message = MessageBuilder::new(self_message_iterator).discarded_events(
+            &self.stream_const(),
+            clock_snapshots,
+            count,
+        )?;
+        self.pending.push_back(message);
+        self.advance(end);
+        Ok(())
+    }
+
+    /// End the open packet, if there is one, and the stream. No more messages can be created
+    /// afterwards.
+    pub fn end(
+        &mut self,
+        self_message_iterator: &BtSelfMessageIterator,
+        clock_snapshot: Option<u64>,
+    ) -> Result<()> {
+        self.check_clock_snapshot(Operation::End, clock_snapshot)?;
+        self.ensure_began(self_message_iterator, clock_snapshot)?;
+        self.close_packet(self_message_iterator, clock_snapshot)?;
+        let message = MessageBuilder::new(self_message_iterator)
+            .stream_end(&self.stream_const(), clock_snapshot)?;
+        self.pending.push_back(message);
+        self.advance(clock_snapshot);
+        self.state = State::Ended;
+        Ok(())
+    }
+}
@@ -21,6 +21,7 @@ pub fn generate_bt_lib(bindgen_file: &PathBuf, output_file: &PathBuf, header_com
     writeln!(writer, "//This file is automatically generated")?;
     writeln!(writer, "#![allow(clippy::upper_case_acronyms)]")?;
     writeln!(writer, "#![allow(clippy::missing_safety_doc)]")?;
+    writeln!(writer, "#![allow(clippy::should_implement_trait)]")?;
     writeln!(writer, "#![allow(non_upper_case_globals)]")?;
     writeln!(writer, "#![allow(non_camel_case_types)]")?;
     writeln!(writer, "#![allow(non_snake_case)]")?;
@@ -127,6 +128,9 @@ impl From< {0:} > for {1:} {{
   }}
 }}
 "#, enum_name, rust_type)?;
+        if enum_name.ends_with("Status") {
+            generate_into_result(writer, &enum_name, &enums.values)?;
+        }
     }
     for key in del_empty_enums {
         result.remove_entry(&key);
@@ -134,6 +138,31 @@ impl From< {0:} > for {1:} {{
     Ok(())
 }
 
+fn generate_into_result(writer: &mut BufWriter<&File>, enum_name: &str, values: &[EnumValue]) -> Result<()> {
+    writeln!(writer, r#"impl {enum_name} {{
+  /// Turn the status into a Result, every status other than Ok is an error.
+  pub fn into_result(self) -> crate::error::Result<()> {{
+    match self {{"#)?;
+    for value in values {
+        let variant = to_camel_case(&value.name);
+        //Some values are not prefixed by exactly the enum name, strip that
+        let kind = match variant.rfind("Status") {
+            Some(pos) if variant.starts_with("Bt") => &variant[pos + "Status".len()..],
+            _ => variant.as_str(),
+        };
+        if kind == "Ok" {
+            writeln!(writer, "      {enum_name}::{variant} => Ok(()),")?;
+        } else {
+            writeln!(
+                writer,
+                r#"      {enum_name}::{variant} => Err(crate::error::Error::new(crate::error::ErrorKind::{kind}, "{enum_name}")),"#
+            )?;
+        }
+    }
+    writeln!(writer, "    }}\n  }}\n}}")?;
+    Ok(())
+}
+
 fn get_enum_primitive_type(enums: &EnumInfo) -> &str {
     match enums.rust_type.as_str() {
         "::std::os::raw::c_int" => "i32",
@@ -178,12 +207,93 @@ fn generate_structs_with_functions(
     rewrite_types(&mut result, enum_types);
     for ti in result.values() {
         generate_fun(writer, ti, true)?;
-        generate_fun(writer, ti, false)?;
+        let mut_methods = generate_fun(writer, ti, false)?;
+        if ti.is_ref_counted() {
+            generate_owned(writer, ti, true, "")?;
+            generate_owned(writer, ti, false, &mut_methods)?;
+        }
+        if ti.is_borrowed {
+            generate_ref(writer, ti, true, "")?;
+            generate_ref(writer, ti, false, &mut_methods)?;
+        }
     }
     Ok(())
 }
 
-fn generate_fun(writer: &mut BufWriter<&File>, ti: &TypeInfo, do_const: bool) -> Result<bool> {
+/// Writes the owned wrapper. It only derefs to the wrapper, so the wrapped pointer can't be
+/// replaced, and gets the methods taking `&mut self` from `mut_methods` instead.
+fn generate_owned(writer: &mut BufWriter<&File>, ti: &TypeInfo, do_const: bool, mut_methods: &str) -> Result<()> {
+    let (suffix, ptr_type) = if do_const { ("Const", "*const") } else { ("", "*mut") };
+    write!(
+        writer,
+        r#"
+/// Owns a reference to a {1:}. The reference is released with put_ref when dropped, and
+/// clones acquire a new reference with get_ref.
+pub struct {0:}{2:}Owned {{
+    inner: {0:}{2:}
+}}
+impl {0:}{2:}Owned {{
+    /// Take over the reference held by ptr, for example the one returned by a create function.
+    pub fn from_ptr(ptr:{3:} {1:}) -> {0:}{2:}Owned {{
+        {0:}{2:}Owned {{ inner: {0:}{2:} {{ ptr }} }}
+    }}
+    /// Acquire a new reference of object.
+    pub fn new_ref(object: &{0:}{2:}) -> {0:}{2:}Owned {{
+        if !object.ptr.is_null() {{
+            object.get_ref();
+        }}
+        {0:}{2:}Owned::from_ptr(object.ptr)
+    }}
+    /// Give up the ownership without releasing the reference.
+    pub fn into_inner(self) -> {0:}{2:} {{
+        let ptr = self.inner.ptr;
+        std::mem::forget(self);
+        {0:}{2:} {{ ptr }}
+    }}
+"#,
+        ti.name, ti.bt_name, suffix, ptr_type
+    )?;
+    if !do_const {
+        write!(
+            writer,
+            r#"    /// Convert into the owned {0:}Const, keeping the reference.
+    pub fn into_const(self) -> {0:}ConstOwned {{
+        {0:}ConstOwned::from_ptr(self.into_inner().ptr)
+    }}
+{1:}"#,
+            ti.name, mut_methods
+        )?;
+    }
+    write!(
+        writer,
+        r#"}}
+impl std::ops::Deref for {0:}{1:}Owned {{
+    type Target = {0:}{1:};
+    fn deref(&self) -> &{0:}{1:} {{
+        &self.inner
+    }}
+}}
+impl Clone for {0:}{1:}Owned {{
+    fn clone(&self) -> Self {{
+        {0:}{1:}Owned::new_ref(&self.inner)
+    }}
+}}
+impl Drop for {0:}{1:}Owned {{
+    fn drop(&mut self) {{
+        if !self.inner.ptr.is_null() {{
+            self.inner.put_ref();
+        }}
+    }}
+}}
+"#,
+        ti.name, suffix
+    )?;
+    Ok(())
+}
+
+/// Writes the wrapper of the pointer and its methods, and returns the methods taking
+/// `&mut self` forwarded to `self.inner`, for the owned and borrowed wrappers
+fn generate_fun(writer: &mut BufWriter<&File>, ti: &TypeInfo, do_const: bool) -> Result<String> {
     if do_const {
         write!(
             writer,
@@ -205,9 +315,13 @@ impl {0:}Const {{
         {0:}Const {{ ptr }}
     }}
     /// Check if the stored pointer is a null pointer or not.
-    pub fn is_empty(&mut self) -> bool {{
+    pub fn is_empty(&self) -> bool {{
         self.ptr.is_null()
     }}
+    /// Get the stored pointer, for example to pass it to a function of the binding.
+    pub fn as_ptr(&self) -> *const {1:} {{
+        self.ptr
+    }}
 "#,
             ti.name, ti.bt_name
         )?;
@@ -231,22 +345,30 @@ impl {0:} {{
         {0:} {{ ptr }}
     }}
     /// Create the equivalent {0:}Const version of this object.
-    pub fn as_const(&mut self) -> {0:}Const {{
+    pub fn as_const(&self) -> {0:}Const {{
       {0:}Const {{ ptr: self.ptr }}
     }}
     /// Generate a {0:}Const pointing to null.
-    pub fn is_empty(&mut self) -> bool {{
+    pub fn is_empty(&self) -> bool {{
         self.ptr.is_null()
     }}
+    /// Get the stored pointer, for example to pass it to a function of the binding.
+    pub fn as_ptr(&self) -> *mut {1:} {{
+        self.ptr
+    }}
 "#,
             ti.name, ti.bt_name
         )?;
     }
 
+    let mut mut_methods = String::new();
     for fi in &ti.functions {
         if do_const && !fi.is_const_self {
             continue;
         }
+        //The reference counting is done by the owned wrappers only
+        let visibility = if fi.name == "get_ref" || fi.name == "put_ref" { "pub(crate)" } else { "pub" };
+        let forward = !fi.is_const_self && !fi.is_create;
         let mut arg_str = if fi.is_const_self {
             "&self".to_string()
         } else if fi.is_create {
@@ -261,6 +383,10 @@ impl {0:} {{
         };
         let mut unsafe_str="";
         for (idx, arg) in fi.args.iter().enumerate() {
+            if arg.is_out {
+                param_str += &format!("{}&mut {}", if idx == 0 && fi.is_create { "" } else { ", " }, arg.name);
+                continue;
+            }
             if idx != 0 {
                 arg_str += &format!(", {}: {}", arg.name, arg.new_type);
                 param_str += &format!(", {}{}{}", arg.pre, arg.name, arg.post);
@@ -271,30 +397,176 @@ impl {0:} {{
                 arg_str += &format!(", {}: {}", arg.name, arg.new_type);
                 param_str += &format!(", {}{}{}", arg.pre, arg.name, arg.post);
             }
-            if arg.full_type.contains("*") || arg.full_type.contains("bt_uuid"){
+            //Wrapped objects and strings are fine, raw pointers are not
+            if arg.new_type.contains('*') || arg.full_type.contains("bt_uuid"){
                 unsafe_str="unsafe "
             }
         }
-        let return_arr = if fi.new_return.is_empty() {
+        let has_strings = fi.args.iter().any(|arg| arg.is_string);
+        let return_arr = if has_strings {
+            let ret = if fi.new_return.is_empty() { "()" } else { &fi.new_return };
+            format!("-> Result<{ret}, std::ffi::NulError>")
+        } else if fi.new_return.is_empty() {
             "".to_string()
         } else {
             format!("-> {}", fi.new_return)
         };
         let null_check=if fi.is_create {""} else {"\n    debug_assert!(!self.ptr.is_null());"};
+        //The strings are converted before the call, as they have to be NUL terminated
+        let prelude = fi
+            .args
+            .iter()
+            .filter(|arg| arg.is_string)
+            .fold(null_check.to_string(), |acc, arg| acc + &format!("\n    let {0:} = {0:}.as_c_str()?;", arg.name));
+        let question_mark = if has_strings { "?" } else { "" };
+        let call_args = fi.args.iter().filter(|arg| !arg.is_out).map(|arg| arg.name.as_str()).collect::<Vec<_>>().join(", ");
+        let receiver = if fi.is_create { "Self::" } else { "self." };
+        //The forwarded methods get the same doc as the methods they forward to
+        let mut forward_method = |name: &str, return_arr: &str, doc: &str| {
+            if forward {
+                mut_methods += &format!(
+                    r#"
+  #[doc(alias = "{0:}")]
+  ///Calls {0:}{doc}
+  pub {unsafe_str}fn {name}({arg_str}) {return_arr} {{
+    self.inner.{name}({call_args})
+  }}
+"#,
+                    fi.bt_name
+                );
+            }
+        };
+        if fi.has_out_args() {
+            let return_type = generate_out_args_fun(writer, fi, unsafe_str, &arg_str, &param_str, &prelude)?;
+            forward_method(&fi.name, &format!("-> {return_type}"), " and returns the values of its out arguments");
+            if fi.is_status {
+                write!(
+                    writer,
+                    r#"
+  #[doc(alias = "{0:}")]
+  ///Calls {0:} and turns the status into a Result
+  pub {unsafe_str}fn try_{1:}({arg_str}) -> crate::error::Result<{2}> {{
+    {receiver}{1:}({call_args}){question_mark}.map_err(|status| status.into_result().unwrap_err())
+  }}
+"#,
+                    fi.bt_name, fi.name, fi.out_return
+                )?;
+                forward_method(
+                    &format!("try_{}", fi.name),
+                    &format!("-> crate::error::Result<{}>", fi.out_return),
+                    " and turns the status into a Result",
+                );
+            }
+            continue;
+        }
         write!(
             writer,
             r#"
   #[doc(alias = "{2:}")]
   ///Calls {2:}
-  pub {unsafe_str}fn {}({arg_str}) {return_arr} {{{null_check}
-    unsafe {{ {}{}({param_str}){} }}
+  {visibility} {unsafe_str}fn {}({arg_str}) {return_arr} {{{prelude}
+    {ok_open}unsafe {{ {}{}({param_str}){} }}{ok_close}
   }}
 "#,
-            fi.name, fi.pre_fn_call, fi.bt_name, fi.post_fn_call
+            fi.name,
+            fi.pre_fn_call,
+            fi.bt_name,
+            fi.post_fn_call,
+            ok_open = if has_strings { "Ok(" } else { "" },
+            ok_close = if has_strings { ")" } else { "" }
         )?;
+        forward_method(&fi.name, &return_arr, "");
+        if fi.is_status {
+            write!(
+                writer,
+                r#"
+  #[doc(alias = "{0:}")]
+  ///Calls {0:} and turns the status into a Result
+  pub {unsafe_str}fn try_{1:}({arg_str}) -> crate::error::Result<()> {{
+    {receiver}{1:}({call_args}){question_mark}.into_result()
+  }}
+"#,
+                fi.bt_name, fi.name
+            )?;
+            forward_method(
+                &format!("try_{}", fi.name),
+                "-> crate::error::Result<()>",
+                " and turns the status into a Result",
+            );
+        }
     }
     write!(writer, "\n}}")?;
-    Ok(true)
+    Ok(mut_methods)
+}
+
+/// Writes a function returning the values of the out arguments: wrapped into a `Result` for
+/// status enums, into an `Option` for availability enums, and as they are otherwise. Returns
+/// the return type of the function.
+fn generate_out_args_fun(
+    writer: &mut BufWriter<&File>,
+    fi: &FuncInfo,
+    unsafe_str: &str,
+    arg_str: &str,
+    param_str: &str,
+    prelude: &str,
+) -> Result<String> {
+    let out_args = fi.args.iter().filter(|arg| arg.is_out);
+    let declarations = out_args
+        .clone()
+        .map(|arg| format!("\n    let mut {}: {} = {};", arg.name, &arg.full_type["*mut ".len()..], arg.out_init))
+        .collect::<String>();
+    let values = out_args.filter(|arg| !arg.out_value.is_empty()).map(|arg| arg.out_value.as_str()).collect::<Vec<_>>();
+    let values = if values.len() == 1 {
+        values[0].to_string()
+    } else {
+        format!("({})", values.join(", "))
+    };
+    let call = format!("unsafe {{ {}{}({param_str}){} }}", fi.pre_fn_call, fi.bt_name, fi.post_fn_call);
+    let (return_type, statement, value) = if fi.bt_return.is_none() {
+        (fi.out_return.clone(), format!("{call};"), values)
+    } else if fi.is_status {
+        (
+            format!("Result<{}, {}>", fi.out_return, fi.new_return),
+            format!("let status: {} = {call};", fi.new_return),
+            format!(
+                "match status {{
+      {} => Ok({values}),
+      status => Err(status),
+    }}",
+                fi.success_variant
+            ),
+        )
+    } else {
+        (
+            format!("Option<{}>", fi.out_return),
+            format!("let result: {} = {call};", fi.new_return),
+            format!(
+                "match result {{
+      {} => Some({values}),
+      _ => None,
+    }}",
+                fi.success_variant
+            ),
+        )
+    };
+    let (return_type, value) = if fi.args.iter().any(|arg| arg.is_string) {
+        (format!("Result<{return_type}, std::ffi::NulError>"), format!("Ok({value})"))
+    } else {
+        (return_type, value)
+    };
+    write!(
+        writer,
+        r#"
+  #[doc(alias = "{0:}")]
+  ///Calls {0:} and returns the values of its out arguments
+  pub {unsafe_str}fn {1:}({arg_str}) -> {return_type} {{{prelude}{declarations}
+    {statement}
+    {value}
+  }}
+"#,
+        fi.bt_name, fi.name
+    )?;
+    Ok(return_type)
 }
 
 fn rewrite_types(result: &mut BTreeMap<String, TypeInfo>, enum_types: &BTreeMap<String, EnumInfo>) {
@@ -324,21 +596,36 @@ fn rewrite_types(result: &mut BTreeMap<String, TypeInfo>, enum_types: &BTreeMap<
                         get_enum_primitive_type(enums)
                     );
                     fi.post_fn_call = ").unwrap()".to_string();
+                    fi.is_status = fi.new_return.ends_with("Status");
                 } else if old_types.contains_key(base_type) {
                     fi.is_const_return = ret.contains("const");
                     fi.new_return = old_types.get(base_type).unwrap().name.to_string();
                     if fi.is_const_return {
                         fi.new_return += "Const";
                     }
-                    fi.pre_fn_call = format!("{}{{ ptr:", fi.new_return);
-                    fi.post_fn_call = "}".to_string();
+                    if fi.is_create && old_types.get(base_type).unwrap().is_ref_counted() {
+                        //Create functions return a new reference
+                        fi.new_return += "Owned";
+                        fi.pre_fn_call = format!("{}::from_ptr(", fi.new_return);
+                        fi.post_fn_call = ")".to_string();
+                    } else if !fi.is_create && fi.name.contains("borrow") {
+                        //Borrowed objects must not outlive the object they are borrowed from
+                        fi.is_borrow = true;
+                        fi.pre_fn_call = format!("{}Ref {{ inner: {} {{ ptr:", fi.new_return, fi.new_return);
+                        fi.post_fn_call = "}, phantom: std::marker::PhantomData }".to_string();
+                        fi.new_return += "Ref<'_>";
+                    } else {
+                        fi.pre_fn_call = format!("{}{{ ptr:", fi.new_return);
+                        fi.post_fn_call = "}".to_string();
+                    }
                 }
             }
             for arg in &mut fi.args {
                 arg.new_type = arg.full_type.to_string();
                 if arg.full_type == "*const ::std::os::raw::c_char" {
-                    arg.new_type = "&str".to_string();
+                    arg.new_type = "&(impl AsCStr + ?Sized)".to_string();
                     arg.post = ".as_ptr()".to_string();
+                    arg.is_string = true;
                     continue;
                 }
                 if arg.full_type == "*const ::std::os::raw::c_char" {
@@ -370,8 +657,198 @@ fn rewrite_types(result: &mut BTreeMap<String, TypeInfo>, enum_types: &BTreeMap<
                     continue;
                 }
             }
+            rewrite_out_args(fi, &old_types, enum_types);
+        }
+    }
+    let mut borrowed = Vec::new();
+    for ti in result.values() {
+        for fi in ti.functions.iter().filter(|fi| fi.is_borrow) {
+            let base_type = fi.bt_return.as_ref().unwrap().split_whitespace().last().unwrap();
+            borrowed.push(base_type.to_string());
+        }
+        for arg in ti.functions.iter().flat_map(|fi| &fi.args).filter(|arg| arg.is_borrow) {
+            borrowed.push(arg.full_type.split_whitespace().last().unwrap().to_string());
+        }
+    }
+    for bt_name in borrowed {
+        result.get_mut(&bt_name).unwrap().is_borrowed = true;
+    }
+}
+
+/// Turns the trailing out arguments of `fi` into return values. This is only done when the
+/// function returns nothing, or an enum with a variant telling that the values were set.
+fn rewrite_out_args(fi: &mut FuncInfo, old_types: &BTreeMap<String, TypeInfo>, enum_types: &BTreeMap<String, EnumInfo>) {
+    if let Some(ret) = &fi.bt_return {
+        match enum_types.get(ret.as_str()).and_then(get_success_variant) {
+            Some(variant) => fi.success_variant = format!("{}::{}", fi.new_return, variant),
+            None => return,
+        }
+    }
+    let creates_ref = fi.is_create || fi.name.contains("copy") || fi.name == "query";
+    let mut first_out = fi.args.len();
+    let mut out_types = Vec::new();
+    while first_out > 0 {
+        let arg = &fi.args[first_out - 1];
+        let pointee = match arg.full_type.strip_prefix("*mut ") {
+            Some(pointee) => pointee,
+            None => break,
+        };
+        //Arrays are returned together with their length in the next argument
+        if first_out >= 2 && pointee == "u64" {
+            let array = &fi.args[first_out - 2];
+            let element = match array.full_type.as_str() {
+                "*mut bt_message_array_const" => {
+                    Some((format!("{}ConstOwned", old_types["bt_message"].name), "std::ptr::null_mut()"))
+                }
+                "*mut bt_field_class_enumeration_mapping_label_array" => Some(("&CStr".to_string(), "std::ptr::null()")),
+                _ => None,
+            };
+            if let Some((element, init)) = element {
+                let convert = if element == "&CStr" {
+                    "CStr::from_ptr(*item)".to_string()
+                } else {
+                    format!("{element}::from_ptr(*item)")
+                };
+                let value = format!(
+                    "if {1:} == 0 {{ Vec::new() }} else {{ unsafe {{ std::slice::from_raw_parts({0:}, {1:} as usize).iter().map(|item| {convert}).collect() }} }}",
+                    array.name, arg.name
+                );
+                let count = &mut fi.args[first_out - 1];
+                count.is_out = true;
+                count.out_init = "0".to_string();
+                let array = &mut fi.args[first_out - 2];
+                array.is_out = true;
+                array.out_init = init.to_string();
+                array.out_value = value;
+                out_types.push(format!("Vec<{element}>"));
+                first_out -= 2;
+                continue;
+            }
         }
+        let name = &arg.name;
+        let (out_type, init, value, is_borrow) = if let Some(enums) = enum_types.get(pointee) {
+            let prim = get_enum_primitive_type(enums);
+            (
+                to_camel_case(&enums.name),
+                "0".to_string(),
+                format!("num::FromPrimitive::from_{prim}({name}).unwrap()"),
+                false,
+            )
+        } else if pointee == "bt_bool" {
+            (
+                "bool".to_string(),
+                "BT_FALSE as bt_bool".to_string(),
+                format!("({name} as u32) != BT_FALSE"),
+                false,
+            )
+        } else if ["u64", "i64", "bt_listener_id", "::std::os::raw::c_uint"].contains(&pointee) {
+            (pointee.to_string(), "0".to_string(), name.to_string(), false)
+        } else if pointee == "*const ::std::os::raw::c_char" {
+            (
+                "Option<&CStr>".to_string(),
+                "std::ptr::null()".to_string(),
+                format!("(!{name}.is_null()).then(|| unsafe {{ CStr::from_ptr({name}) }})"),
+                false,
+            )
+        } else {
+            let base_type = pointee.split_whitespace().last().unwrap();
+            let ti = match old_types.get(base_type) {
+                Some(ti) if pointee.starts_with('*') => ti,
+                _ => break,
+            };
+            let (wrapper, init) = if pointee.starts_with("*const") {
+                (format!("{}Const", ti.name), "std::ptr::null()")
+            } else {
+                (ti.name.to_string(), "std::ptr::null_mut()")
+            };
+            if creates_ref && ti.is_ref_counted() {
+                //The out argument holds a new reference
+                (format!("{wrapper}Owned"), init.to_string(), format!("{wrapper}Owned::from_ptr({name})"), false)
+            } else if fi.name.contains("borrow") {
+                (
+                    format!("{wrapper}Ref<'_>"),
+                    init.to_string(),
+                    format!("{wrapper}Ref {{ inner: {wrapper} {{ ptr: {name} }}, phantom: std::marker::PhantomData }}"),
+                    true,
+                )
+            } else {
+                (wrapper.clone(), init.to_string(), format!("{wrapper} {{ ptr: {name} }}"), false)
+            }
+        };
+        let arg = &mut fi.args[first_out - 1];
+        arg.is_out = true;
+        arg.out_init = init;
+        arg.out_value = value;
+        arg.is_borrow = is_borrow;
+        out_types.push(out_type);
+        first_out -= 1;
+    }
+    if out_types.is_empty() {
+        fi.success_variant.clear();
+        return;
+    }
+    out_types.reverse();
+    fi.out_return = if out_types.len() == 1 {
+        out_types.remove(0)
+    } else {
+        format!("({})", out_types.join(", "))
+    };
+}
+
+/// The variant of a status or availability enum telling that the out arguments were set
+fn get_success_variant(enums: &EnumInfo) -> Option<String> {
+    enums
+        .values
+        .iter()
+        .map(|value| to_camel_case(&value.name))
+        .find(|variant| {
+            let kind = match variant.rfind("Status") {
+                Some(pos) if variant.starts_with("Bt") => &variant[pos + "Status".len()..],
+                _ => variant.as_str(),
+            };
+            kind == "Ok" || kind == "Available" || kind == "Known"
+        })
+}
+
+/// Writes the borrowed wrapper. Like the owned wrapper, it only derefs to the wrapper and gets
+/// the methods taking `&mut self` from `mut_methods`.
+fn generate_ref(writer: &mut BufWriter<&File>, ti: &TypeInfo, do_const: bool, mut_methods: &str) -> Result<()> {
+    let suffix = if do_const { "Const" } else { "" };
+    write!(
+        writer,
+        r#"
+/// A {1:} borrowed from another object. The lifetime ensures that it can't be used after
+/// the object it was borrowed from is gone.
+pub struct {0:}{2:}Ref<'a> {{
+    inner: {0:}{2:},
+    phantom: std::marker::PhantomData<&'a ()>
+}}
+impl<'a> std::ops::Deref for {0:}{2:}Ref<'a> {{
+    type Target = {0:}{2:};
+    fn deref(&self) -> &{0:}{2:} {{
+        &self.inner
+    }}
+}}
+"#,
+        ti.name, ti.bt_name, suffix
+    )?;
+    if !mut_methods.is_empty() {
+        writeln!(writer, "impl<'a> {0:}Ref<'a> {{{1:}}}", ti.name, mut_methods)?;
+    }
+    if ti.is_ref_counted() {
+        write!(
+            writer,
+            r#"impl<'a> {0:}{1:}Ref<'a> {{
+    /// Acquire a new reference, which can outlive the object it was borrowed from.
+    pub fn to_owned(&self) -> {0:}{1:}Owned {{
+        {0:}{1:}Owned::new_ref(&self.inner)
+    }}
+}}
+"#,
+            ti.name, suffix
+        )?;
     }
+    Ok(())
 }
 
 #[derive(Debug, Default, Clone)]
@@ -381,6 +858,11 @@ struct ArgInfo {
     new_type: String,
     pre: String,
     post: String,
+    is_string: bool,
+    is_out: bool,
+    out_init: String,
+    out_value: String,
+    is_borrow: bool,
 }
 
 #[derive(Debug, Default, Clone)]
@@ -413,11 +895,18 @@ struct FuncInfo {
     is_const_self: bool,
     is_const_return: bool,
     is_create: bool,
+    is_borrow: bool,
+    is_status: bool,
+    out_return: String,
+    success_variant: String,
 }
 impl FuncInfo {
     pub fn add_argument(&mut self, arg: ArgInfo) {
         self.args.push(arg);
     }
+    pub fn has_out_args(&self) -> bool {
+        self.args.iter().any(|arg| arg.is_out)
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -425,11 +914,16 @@ struct TypeInfo {
     name: String,
     bt_name: String,
     functions: Vec<FuncInfo>,
+    is_borrowed: bool,
 }
 impl TypeInfo {
     pub fn add_function(&mut self, func: FuncInfo) {
         self.functions.push(func);
     }
+    pub fn is_ref_counted(&self) -> bool {
+        self.functions.iter().any(|f| f.name == "get_ref")
+            && self.functions.iter().any(|f| f.name == "put_ref")
+    }
 }
 
 pub fn to_camel_case(arg: &str) -> String {
@@ -542,7 +1036,7 @@ fn get_or_create_typeinfo<'a>(
         let new_type = TypeInfo {
             name: to_camel_case(self_type),
             bt_name: self_type.to_string(),
-            functions: Vec::new(),
+            ..Default::default()
         };
         types.insert(self_type.to_string(), new_type);
         types.get_mut(self_type).unwrap()
@@ -0,0 +1,280 @@
+// Copyright 2023 - 2023, Karsten Becker and the rust babeltrace2-plugin contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+//! Reading the messages of an upstream message iterator
+//!
+//! An [`UpstreamIterator`] owns a message iterator and the messages it returned, which are
+//! released when they are dropped. It is an `Iterator` over the messages, and
+//! [`UpstreamIterator::next_batch`] borrows a whole batch. The batch buffer is reused, so no
+//! allocation is needed per batch.
+//!
+//! The message iterators of filters create an [`UpstreamIterators`] in their initialize
+//! method, which holds an upstream iterator per connected input port until it is dropped
+//! along with the message iterator.
+//!
+//! ```ignore
+//! loop {
+//!     match upstream.poll_next()? {
+//!         MessagePoll::Ready(message) => handle(&message),
+//!         MessagePoll::Again => return Ok(BtComponentClassSinkConsumeMethodStatus::Again),
+//!         MessagePoll::End => return Ok(BtComponentClassSinkConsumeMethodStatus::End),
+//!     }
+//! }
+//! ```
+use std::collections::VecDeque;
+
+use crate::bt2::binding::*;
+use crate::bt2::*;
+use crate::error::{Error, ErrorKind, Result};
+
+/// What an upstream message iterator returned
+#[derive(Debug, PartialEq, Eq)]
+pub enum MessagePoll<T> {
+    Ready(T),
+    /// No messages are available now, try again later
+    Again,
+    /// There are no more messages
+    End,
+}
+
+/// An upstream message iterator with the messages of its current batch
+pub struct UpstreamIterator {
+    iterator: BtMessageIteratorOwned,
+    batch: VecDeque<BtMessageConstOwned>,
+    batch_returned: bool,
+    ended: bool,
+}
+
+impl UpstreamIterator {
+    pub fn new(iterator: BtMessageIteratorOwned) -> UpstreamIterator {
+        UpstreamIterator {
+            iterator,
+            batch: VecDeque::new(),
+            batch_returned: false,
+            ended: false,
+        }
+    }
+
+    /// The message iterator, seek with the methods of the upstream iterator instead
+    pub fn iterator(&self) -> &BtMessageIterator {
+        &self.iterator
+    }
+
+    /// Forget the messages fetched before a seek
+    fn reset(&mut self) {
+        self.batch.clear();
+        self.batch_returned = false;
+        self.ended = false;
+    }
+
+    pub fn can_seek_beginning(&mut self) -> Result<bool> {
+        self.iterator.try_can_seek_beginning()
+    }
+
+    /// Seek the beginning, the messages fetched before are released
+    pub fn seek_beginning(&mut self) -> Result<()> {
+        self.iterator.try_seek_beginning()?;
+        self.reset();
+        Ok(())
+    }
+
+    pub fn can_seek_ns_from_origin(&mut self, ns_from_origin: i64) -> Result<bool> {
+        self.iterator.try_can_seek_ns_from_origin(ns_from_origin)
+    }
+
+    /// Seek the time `ns_from_origin`, the messages fetched before are released
+    pub fn seek_ns_from_origin(&mut self, ns_from_origin: i64) -> Result<()> {
+        self.iterator.try_seek_ns_from_origin(ns_from_origin)?;
+        self.reset();
+        Ok(())
+    }
+
+    pub fn can_seek_forward(&mut self) -> bool {
+        self.iterator.can_seek_forward()
+    }
+
+    /// The upstream message iterator ended and all its messages were returned
+    pub fn is_ended(&self) -> bool {
+        self.ended && (self.batch.is_empty() || self.batch_returned)
+    }
+
+    /// Release the batch returned by `next_batch`
+    pub(crate) fn release_returned_batch(&mut self) {
+        if self.batch_returned {
+            self.batch.clear();
+            self.batch_returned = false;
+        }
+    }
+
+    /// Get the next batch into the buffer, once the iterator ended it is not called again
+    fn fetch(&mut self) -> Result<MessagePoll<()>> {
+        while self.batch.is_empty() {
+            if self.ended {
+                return Ok(MessagePoll::End);
+            }
+            let mut messages: bt_message_array_const = std::ptr::null_mut();
+            let mut count: u64 = 0;
+            let status: BtMessageIteratorNextStatus = unsafe {
+                bt_message_iterator_next(self.iterator.as_ptr(), &mut messages, &mut count)
+            }
+            .into();
+            match status {
+                BtMessageIteratorNextStatus::Ok => {
+                    if count > 0 {
+                        // The references of the messages are passed to the caller
+                        let messages =
+                            unsafe { std::slice::from_raw_parts(messages, count as usize) };
+                        self.batch.extend(
+                            messages
+                                .iter()
+                                .map(|message| BtMessageConstOwned::from_ptr(*message)),
+                        );
+                    }
+                }
+                BtMessageIteratorNextStatus::Again => return Ok(MessagePoll::Again),
+                BtMessageIteratorNextStatus::End => {
+                    self.ended = true;
+                    return Ok(MessagePoll::End);
+                }
+                status => status.into_result()?,
+            }
+        }
+        Ok(MessagePoll::Ready(()))
+    }
+
+    /// The next message
+    pub fn poll_next(&mut self) -> Result<MessagePoll<BtMessageConstOwned>> {
+        self.release_returned_batch();
+        Ok(match self.fetch()? {
+            MessagePoll::Ready(()) => MessagePoll::Ready(
+                self.batch
+                    .pop_front()
+                    .expect("A fetched batch is not empty"),
+            ),
+            MessagePoll::Again => MessagePoll::Again,
+            MessagePoll::End => MessagePoll::End,
+        })
+    }
+
+    /// The messages not returned yet of the current batch, or the next batch. The messages are
+    /// released on the next call of `next_batch` or `poll_next`.
+    pub fn next_batch(&mut self) -> Result<MessagePoll<&[BtMessageConstOwned]>> {
+        self.release_returned_batch();
+        Ok(match self.fetch()? {
+            MessagePoll::Ready(()) => {
+                self.batch_returned = true;
+                MessagePoll::Ready(self.batch.make_contiguous())
+            }
+            MessagePoll::Again => MessagePoll::Again,
+            MessagePoll::End => MessagePoll::End,
+        })
+    }
+}
+
+/// Ends when the upstream message iterator ends. `Again` is an error of kind
+/// [`ErrorKind::Again`], use [`UpstreamIterator::poll_next`] to handle it without an error.
+impl Iterator for UpstreamIterator {
+    type Item = Result<BtMessageConstOwned>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.poll_next() {
+            Ok(MessagePoll::Ready(message)) => Some(Ok(message)),
+            Ok(MessagePoll::Again) => Some(Err(Error::new(
+                ErrorKind::Again,
+                "BtMessageIteratorNextStatus",
+            ))),
+            Ok(MessagePoll::End) => None,
+            Err(error) => Some(Err(error)),
+        }
+    }
+}
+
+/// The upstream message iterators of a filter message iterator, one per connected input port
+pub struct UpstreamIterators {
+    iterators: Vec<(String, UpstreamIterator)>,
+}
+
+impl UpstreamIterators {
+    /// Create a message iterator on each connected input port of the filter component of
+    /// `self_message_iterator`. Fails with `Error`, after appending an error cause, if the
+    /// component is not a filter.
+    pub fn create(
+        self_message_iterator: &mut BtSelfMessageIterator,
+    ) -> std::result::Result<UpstreamIterators, BtMessageIteratorClassInitializeMethodStatus> {
+        // The component is borrowed from a second handle, the iterators are created with the
+        // first
+        let mut component_of = BtSelfMessageIterator::from_ptr(self_message_iterator.as_ptr());
+        let mut self_component = component_of.borrow_component();
+        if !self_component.as_component_inline().is_filter_inline() {
+            crate::bt_error_cause!(
+                self_message_iterator,
+                "Upstream iterators can only be created by the message iterators of filters"
+            );
+            return Err(BtMessageIteratorClassInitializeMethodStatus::Error);
+        }
+        // Safety: the component is a filter
+        let mut filter = unsafe { self_component.as_self_component_filter_unchecked() };
+        let mut iterators = Vec::new();
+        for index in 0..filter.as_component_filter_inline().get_input_port_count() {
+            let port = filter.borrow_input_port_by_index(index);
+            if !port
+                .as_port_input_inline()
+                .as_port_const_inline()
+                .is_connected()
+            {
+                continue;
+            }
+            let name = port
+                .as_port_input_inline()
+                .as_port_const_inline()
+                .get_name()
+                .to_string_lossy()
+                .into_owned();
+            let iterator =
+                BtMessageIterator::create_from_message_iterator(self_message_iterator, &port)?;
+            iterators.push((name, UpstreamIterator::new(iterator)));
+        }
+        Ok(UpstreamIterators { iterators })
+    }
+
+    /// The upstream message iterators, in the order of the input ports
+    pub fn into_iterators(self) -> Vec<UpstreamIterator> {
+        self.iterators
+            .into_iter()
+            .map(|(_, iterator)| iterator)
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.iterators.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.iterators.is_empty()
+    }
+
+    /// All upstream message iterators ended
+    pub fn is_ended(&self) -> bool {
+        self.iterators
+            .iter()
+            .all(|(_, iterator)| iterator.is_ended())
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut UpstreamIterator> {
+        self.iterators.get_mut(index).map(|(_, iterator)| iterator)
+    }
+
+    /// The upstream message iterator of the input port `port_name`
+    pub fn by_port_name(&mut self, port_name: &str) -> Option<&mut UpstreamIterator> {
+        self.iterators
+            .iter_mut()
+            .find(|(name, _)| name == port_name)
+            .map(|(_, iterator)| iterator)
+    }
+
+    /// The upstream message iterators with the names of their input ports
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&str, &mut UpstreamIterator)> {
+        self.iterators
+            .iter_mut()
+            .map(|(name, iterator)| (name.as_str(), iterator))
+    }
+}
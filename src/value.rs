@@ -0,0 +1,303 @@
+// Copyright 2023 - 2023, Karsten Becker and the rust babeltrace2-plugin contributors
+// SPDX-License-Identifier: GPL-2.0-or-later
+//! Conversions between values and Rust types, and iteration over arrays and maps
+//!
+//! Owned values are created with `TryFrom`, for example `BtValueOwned::try_from(vec![1u64, 2])`,
+//! which fails with [`ErrorKind::MemoryError`] if a value could not be created, and for strings
+//! and keys containing a NUL character. Values are converted back with
+//! `TryFrom<&BtValueConst>`, which fails with [`ErrorKind::TypeMismatch`] for values of
+//! another type.
+use std::any::Any;
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_void};
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::bt2::binding::*;
+use crate::bt2::*;
+use crate::error::{Error, ErrorKind, Result};
+
+struct ForEachEntry<F, E> {
+    f: F,
+    error: Option<E>,
+    panic: Option<Box<dyn Any + Send>>,
+}
+
+/// Calls the closure of a [`ForEachEntry`], a panic is resumed after the iteration
+unsafe extern "C" fn for_each_entry_trampoline<F, E>(
+    key: *const c_char,
+    value: *const bt_value,
+    user_data: *mut c_void,
+) -> bt_value_map_foreach_entry_const_func_status
+where
+    F: FnMut(*const c_char, *const bt_value) -> std::result::Result<(), E>,
+{
+    let data = &mut *(user_data as *mut ForEachEntry<F, E>);
+    match panic::catch_unwind(AssertUnwindSafe(|| (data.f)(key, value))) {
+        Ok(Ok(())) => BtValueMapForeachEntryConstFuncStatus::Ok.into(),
+        Ok(Err(error)) => {
+            data.error = Some(error);
+            BtValueMapForeachEntryConstFuncStatus::Interrupt.into()
+        }
+        Err(payload) => {
+            data.panic = Some(payload);
+            BtValueMapForeachEntryConstFuncStatus::Interrupt.into()
+        }
+    }
+}
+
+/// Call `f` with the key and value pointers of each entry of `map`, until it returns an error.
+/// The pointers live as long as the map.
+pub(crate) fn for_each_entry_ptr<F, E>(map: *const bt_value, f: F) -> std::result::Result<(), E>
+where
+    F: FnMut(*const c_char, *const bt_value) -> std::result::Result<(), E>,
+{
+    let mut data = ForEachEntry {
+        f,
+        error: None,
+        panic: None,
+    };
+    unsafe {
+        bt_value_map_foreach_entry_const(
+            map,
+            Some(for_each_entry_trampoline::<F, E>),
+            &mut data as *mut ForEachEntry<F, E> as *mut c_void,
+        );
+    }
+    if let Some(payload) = data.panic {
+        panic::resume_unwind(payload);
+    }
+    match data.error {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
+
+impl BtValueConst {
+    /// The elements of an array value
+    pub fn array_elements(&self) -> impl Iterator<Item = BtValueConstRef<'_>> {
+        (0..self.array_get_length())
+            .map(move |index| self.array_borrow_element_by_index_const(index))
+    }
+
+    /// Call `f` with each entry of a map value, until it returns an error, which is returned
+    pub fn map_try_for_each_entry<F, E>(&self, mut f: F) -> std::result::Result<(), E>
+    where
+        F: FnMut(&CStr, &BtValueConst) -> std::result::Result<(), E>,
+    {
+        for_each_entry_ptr(self.as_ptr(), |key, value| {
+            f(
+                unsafe { CStr::from_ptr(key) },
+                &BtValueConst::from_ptr(value),
+            )
+        })
+    }
+
+    /// The keys of a map value, in no particular order
+    pub fn map_keys(&self) -> Vec<&CStr> {
+        let mut keys = Vec::new();
+        let _ = for_each_entry_ptr(self.as_ptr(), |key, _| {
+            keys.push(unsafe { CStr::from_ptr(key) });
+            Ok::<(), ()>(())
+        });
+        keys
+    }
+
+    /// The entries of a map value, in no particular order
+    pub fn map_entries(&self) -> impl Iterator<Item = (&CStr, BtValueConstRef<'_>)> {
+        let mut entries = Vec::new();
+        let _ = for_each_entry_ptr(self.as_ptr(), |key, value| {
+            // The keys and values live as long as the map
+            entries.push(unsafe { (CStr::from_ptr(key), BtValueConstRef::borrow_ptr(value)) });
+            Ok::<(), ()>(())
+        });
+        entries.into_iter()
+    }
+}
+
+/// Fails with [`ErrorKind::MemoryError`] if `value` could not be created
+fn created(value: BtValueOwned) -> Result<BtValueOwned> {
+    if value.is_empty() {
+        Err(Error::new(ErrorKind::MemoryError, "BtValue"))
+    } else {
+        Ok(value)
+    }
+}
+
+impl TryFrom<bool> for BtValueOwned {
+    type Error = Error;
+    fn try_from(value: bool) -> Result<Self> {
+        created(BtValue::bool_create_init(value))
+    }
+}
+
+impl TryFrom<u64> for BtValueOwned {
+    type Error = Error;
+    fn try_from(value: u64) -> Result<Self> {
+        created(BtValue::integer_unsigned_create_init(value))
+    }
+}
+
+impl TryFrom<i64> for BtValueOwned {
+    type Error = Error;
+    fn try_from(value: i64) -> Result<Self> {
+        created(BtValue::integer_signed_create_init(value))
+    }
+}
+
+impl TryFrom<f64> for BtValueOwned {
+    type Error = Error;
+    fn try_from(value: f64) -> Result<Self> {
+        created(BtValue::real_create_init(value))
+    }
+}
+
+/// Fails if `value` contains a NUL character
+impl TryFrom<&str> for BtValueOwned {
+    type Error = Error;
+    fn try_from(value: &str) -> Result<Self> {
+        created(BtValue::string_create_init(value)?)
+    }
+}
+
+/// Fails if `value` contains a NUL character
+impl TryFrom<String> for BtValueOwned {
+    type Error = Error;
+    fn try_from(value: String) -> Result<Self> {
+        value.as_str().try_into()
+    }
+}
+
+impl<T> TryFrom<Vec<T>> for BtValueOwned
+where
+    T: TryInto<BtValueOwned>,
+    Error: From<T::Error>,
+{
+    type Error = Error;
+    fn try_from(value: Vec<T>) -> Result<Self> {
+        let mut array = created(BtValue::array_create())?;
+        for element in value {
+            let element: BtValueOwned = element.try_into()?;
+            array.try_array_append_element(&element)?;
+        }
+        Ok(array)
+    }
+}
+
+/// Fails if a key contains a NUL character
+impl<T> TryFrom<HashMap<String, T>> for BtValueOwned
+where
+    T: TryInto<BtValueOwned>,
+    Error: From<T::Error>,
+{
+    type Error = Error;
+    fn try_from(value: HashMap<String, T>) -> Result<Self> {
+        let mut map = created(BtValue::map_create())?;
+        for (key, entry) in value {
+            let entry: BtValueOwned = entry.try_into()?;
+            map.try_map_insert_entry(&key, &entry)?;
+        }
+        Ok(map)
+    }
+}
+
+fn type_mismatch() -> Error {
+    Error::new(ErrorKind::TypeMismatch, "BtValue")
+}
+
+impl TryFrom<&BtValueConst> for bool {
+    type Error = Error;
+    fn try_from(value: &BtValueConst) -> Result<Self> {
+        match value.get_type() {
+            BtValueType::Bool => Ok(value.bool_get()),
+            _ => Err(type_mismatch()),
+        }
+    }
+}
+
+/// Signed integers are converted if they are not negative
+impl TryFrom<&BtValueConst> for u64 {
+    type Error = Error;
+    fn try_from(value: &BtValueConst) -> Result<Self> {
+        match value.get_type() {
+            BtValueType::UnsignedInteger => Ok(value.integer_unsigned_get()),
+            BtValueType::SignedInteger => u64::try_from(value.integer_signed_get())
+                .map_err(|_| Error::new(ErrorKind::OverflowError, "BtValue")),
+            _ => Err(type_mismatch()),
+        }
+    }
+}
+
+/// Unsigned integers are converted if they are not too large
+impl TryFrom<&BtValueConst> for i64 {
+    type Error = Error;
+    fn try_from(value: &BtValueConst) -> Result<Self> {
+        match value.get_type() {
+            BtValueType::SignedInteger => Ok(value.integer_signed_get()),
+            BtValueType::UnsignedInteger => i64::try_from(value.integer_unsigned_get())
+                .map_err(|_| Error::new(ErrorKind::OverflowError, "BtValue")),
+            _ => Err(type_mismatch()),
+        }
+    }
+}
+
+/// Integers are converted too
+impl TryFrom<&BtValueConst> for f64 {
+    type Error = Error;
+    fn try_from(value: &BtValueConst) -> Result<Self> {
+        match value.get_type() {
+            BtValueType::Real => Ok(value.real_get()),
+            BtValueType::UnsignedInteger => Ok(value.integer_unsigned_get() as f64),
+            BtValueType::SignedInteger => Ok(value.integer_signed_get() as f64),
+            _ => Err(type_mismatch()),
+        }
+    }
+}
+
+/// Invalid UTF-8 sequences are replaced
+impl TryFrom<&BtValueConst> for String {
+    type Error = Error;
+    fn try_from(value: &BtValueConst) -> Result<Self> {
+        match value.get_type() {
+            BtValueType::String => Ok(value.string_get().to_string_lossy().into_owned()),
+            _ => Err(type_mismatch()),
+        }
+    }
+}
+
+impl<T> TryFrom<&BtValueConst> for Vec<T>
+where
+    T: for<'a> TryFrom<&'a BtValueConst, Error = Error>,
+{
+    type Error = Error;
+    fn try_from(value: &BtValueConst) -> Result<Self> {
+        match value.get_type() {
+            BtValueType::Array => value
+                .array_elements()
+                .map(|element| T::try_from(&element))
+                .collect(),
+            _ => Err(type_mismatch()),
+        }
+    }
+}
+
+/// Invalid UTF-8 sequences of the keys are replaced
+impl<T> TryFrom<&BtValueConst> for HashMap<String, T>
+where
+    T: for<'a> TryFrom<&'a BtValueConst, Error = Error>,
+{
+    type Error = Error;
+    fn try_from(value: &BtValueConst) -> Result<Self> {
+        match value.get_type() {
+            BtValueType::Map => {
+                let mut map = HashMap::new();
+                value.map_try_for_each_entry(|key, entry| -> Result<()> {
+                    map.insert(key.to_string_lossy().into_owned(), T::try_from(entry)?);
+                    Ok(())
+                })?;
+                Ok(map)
+            }
+            _ => Err(type_mismatch()),
+        }
+    }
+}